@@ -2,6 +2,13 @@ use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
 
+use crate::{
+    codegen::Language,
+    export::ExportFormat,
+    into_expr_tree::{ImplicitMultiplication, LiteralEncoding, VariableOrder},
+    parser::Syntax,
+};
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Arguments {
@@ -14,8 +21,20 @@ pub enum RootSubcommand {
     /// Evaluates a fef Single Formula file using a f64 interpreter
     Evaluate(Evaluate),
 
-    /// Creates a new fef Single Formula file from user input
+    /// Creates a new fef Single Formula file from a formula given as an expression, in a file or by the user
     Create(Create),
+
+    /// Prints the formula of a fef Single Formula or Raw Formula file in the expression language
+    Decompile(Decompile),
+
+    /// Prints the version, configuration, metadata and expression of a fef file along with their bytes
+    Inspect(Inspect),
+
+    /// Writes the formula of a fef Single Formula or Raw Formula file in another notation, such as LaTeX
+    Export(Export),
+
+    /// Generates source code of a function computing the formula of a fef Single Formula or Raw Formula file
+    Codegen(Codegen),
 }
 
 #[derive(Parser, Debug)]
@@ -23,6 +42,26 @@ pub struct Evaluate {
     /// The path to the fef file to evaluate
     #[clap(short, long)]
     pub input: PathBuf,
+
+    /// Value of a variable given as `name=value`, or `#id=value` for a variable given by its identifier, can be repeated
+    #[clap(long = "var", value_name = "NAME=VALUE", value_parser = parse_variable_value)]
+    pub vars: Vec<(String, f64)>,
+
+    /// Path to a file with one `name=value` pair per line, values given with `--var` take precedence
+    #[clap(long)]
+    pub vars_file: Option<PathBuf>,
+
+    /// Fail instead of prompting for values of variables that were not given
+    #[clap(long)]
+    pub no_prompt: bool,
+
+    /// Path to a CSV file to evaluate the formula for each row of, columns are matched to variables by their header
+    #[clap(long)]
+    pub csv: Option<PathBuf>,
+
+    /// Path to write the CSV file with results to, the standard output is used if not given
+    #[clap(long, requires = "csv")]
+    pub out: Option<PathBuf>,
 }
 
 #[derive(Parser, Debug)]
@@ -32,6 +71,148 @@ pub struct Create {
     pub output: PathBuf,
 
     /// Path to a string formula file
-    #[clap(short, long)]
+    #[clap(short, long, conflicts_with = "expr")]
     pub input: Option<PathBuf>,
+
+    /// The formula, given directly instead of in a file
+    #[clap(short, long, allow_hyphen_values = true)]
+    pub expr: Option<String>,
+
+    /// Name of the formula
+    #[clap(short, long, conflicts_with = "no_name")]
+    pub name: Option<String>,
+
+    /// Create the formula without a name instead of prompting for it
+    #[clap(long)]
+    pub no_name: bool,
+
+    /// Notation the formula is written in
+    #[clap(long, value_enum, default_value_t)]
+    pub syntax: Syntax,
+
+    /// How number literals are encoded
+    #[clap(long, value_enum, default_value_t)]
+    pub literals: LiteralEncoding,
+
+    /// Keep negated number literals such as `-5` as negations instead of writing negative literals
+    #[clap(long)]
+    pub no_fold_negative_literals: bool,
+
+    /// Whether adjacent expressions such as `2x` are multiplied [default: off, on with `--syntax latex`]
+    #[clap(long, value_enum)]
+    pub implicit_multiplication: Option<ImplicitMultiplication>,
+
+    /// Read names of built-in constants such as `pi` and `e` as variables
+    #[clap(long)]
+    pub no_builtin_constants: bool,
+
+    /// Comma separated names of the variables of the formula, which get the first identifiers in this order
+    #[clap(long, value_name = "NAMES", value_delimiter = ',')]
+    pub variables: Vec<String>,
+
+    /// Order in which the variables that were not declared get their identifiers
+    #[clap(long, value_enum, default_value_t)]
+    pub variable_order: VariableOrder,
+
+    /// Reject variables that were not declared with `--variables` or a `var` declaration
+    #[clap(long)]
+    pub strict_variables: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct Decompile {
+    /// The path to the fef file to decompile
+    #[clap(short, long)]
+    pub input: PathBuf,
+
+    /// Leave out the header comment with the name of the formula
+    #[clap(long)]
+    pub no_header: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct Inspect {
+    /// The path to the fef file to inspect
+    #[clap(short, long)]
+    pub input: PathBuf,
+
+    /// Print the inspection as JSON
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct Export {
+    /// The path to the fef file to export
+    #[clap(short, long)]
+    pub input: PathBuf,
+
+    /// Notation to write the formula in
+    #[clap(short, long, value_enum)]
+    pub format: ExportFormat,
+
+    /// Path to write the formula to, the standard output is used if not given
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+pub struct Codegen {
+    /// The path to the fef file to generate code from
+    #[clap(short, long)]
+    pub input: PathBuf,
+
+    /// Language of the generated code
+    #[clap(short, long, value_enum)]
+    pub lang: Language,
+
+    /// Path to write the code to, with the extension of each file for C, the standard output is used if not given
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Also generate tests checking the function against the fefy interpreter on sample inputs, only for Rust
+    #[clap(long)]
+    pub tests: bool,
+
+    /// Also generate a variant of the function taking a struct of the variables, only for C
+    #[clap(long = "struct")]
+    pub variables_struct: bool,
+}
+
+/// Parses a `name=value` pair assigning a value to a variable
+pub fn parse_variable_value(s: &str) -> Result<(String, f64), String> {
+    let (name, value) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected `name=value`, found `{}`", s))?;
+    let value = value
+        .trim()
+        .parse::<f64>()
+        .map_err(|error| format!("invalid value of variable `{}`: {}", name.trim(), error))?;
+    Ok((name.trim().to_string(), value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_variable_value;
+
+    #[test]
+    fn variable_values_are_name_value_pairs() {
+        assert_eq!(parse_variable_value("x=1.5"), Ok(("x".to_string(), 1.5)));
+        assert_eq!(
+            parse_variable_value(" x = -2 "),
+            Ok(("x".to_string(), -2.0))
+        );
+        assert_eq!(
+            parse_variable_value("#3=1e3"),
+            Ok(("#3".to_string(), 1000.0))
+        );
+        // The value follows the last `=`, so quoted names can contain one
+        assert_eq!(
+            parse_variable_value("\"a=b\"=4"),
+            Ok(("\"a=b\"".to_string(), 4.0))
+        );
+        assert!(parse_variable_value("x").is_err());
+        assert!(parse_variable_value("x=").is_err());
+        assert!(parse_variable_value("x=one").is_err());
+    }
 }
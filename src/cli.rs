@@ -1,21 +1,91 @@
 use anyhow::Result;
 
 use crate::{
-    arguments::{Arguments, Create, Evaluate, RootSubcommand},
+    arguments::{Arguments, Codegen, Create, Decompile, Evaluate, Export, Inspect, RootSubcommand},
+    codegen::CodegenOptions,
     evaluate_from_file,
-    tasks::write_to_file_from_file,
-    write_to_file_from_stdin,
+    into_expr_tree::{ImplicitMultiplication, ParseOptions},
+    parser::Syntax,
+    tasks::{
+        codegen_file, create_file, decompile_file, evaluate_csv_from_file, export_file,
+        inspect_file,
+    },
 };
 
 pub(crate) fn evaluate(arguments: Arguments) -> Result<()> {
     match arguments.subcommand {
-        RootSubcommand::Create(Create { output, input }) => {
-            if let Some(input) = input {
-                write_to_file_from_file(&input, &output)
+        RootSubcommand::Create(Create {
+            output,
+            input,
+            expr,
+            name,
+            no_name,
+            syntax,
+            literals,
+            no_fold_negative_literals,
+            implicit_multiplication,
+            no_builtin_constants,
+            variables,
+            variable_order,
+            strict_variables,
+        }) => create_file(
+            &output,
+            input.as_deref(),
+            expr,
+            name,
+            no_name,
+            syntax,
+            &ParseOptions {
+                literals,
+                fold_negative_literals: !no_fold_negative_literals,
+                // LaTeX formulas are usually written with implicit multiplication, as in `2\pi r`
+                implicit_multiplication: implicit_multiplication.unwrap_or(match syntax {
+                    Syntax::Expr => ImplicitMultiplication::Off,
+                    Syntax::Latex => ImplicitMultiplication::On,
+                }),
+                builtin_constants: !no_builtin_constants,
+                variables,
+                variable_order,
+                strict_variables,
+            },
+        ),
+        RootSubcommand::Evaluate(Evaluate {
+            input,
+            vars,
+            vars_file,
+            no_prompt,
+            csv,
+            out,
+        }) => {
+            if let Some(csv) = csv {
+                evaluate_csv_from_file(&input, vars_file.as_deref(), &vars, &csv, out.as_deref())
             } else {
-                write_to_file_from_stdin(&output)
+                evaluate_from_file(&input, vars_file.as_deref(), &vars, !no_prompt)
             }
         }
-        RootSubcommand::Evaluate(Evaluate { input }) => evaluate_from_file(&input),
+        RootSubcommand::Decompile(Decompile { input, no_header }) => {
+            decompile_file(&input, !no_header)
+        }
+        RootSubcommand::Inspect(Inspect { input, json }) => inspect_file(&input, json),
+        RootSubcommand::Export(Export {
+            input,
+            format,
+            output,
+        }) => export_file(&input, format, output.as_deref()),
+        RootSubcommand::Codegen(Codegen {
+            input,
+            lang,
+            output,
+            tests,
+            variables_struct,
+        }) => codegen_file(
+            &input,
+            lang,
+            output.as_deref(),
+            &CodegenOptions {
+                tests,
+                variables_struct,
+            },
+        ),
     }
 }
@@ -0,0 +1,541 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{bail, Result};
+use fef::v0::{
+    expr::{
+        traits::{BinaryOperationExpr, ExprObj, UnaryOperationExpr},
+        Expr, ExprTree,
+    },
+    raw::VariableLengthEnum,
+};
+use phf::{phf_set, Set};
+
+use super::{
+    comment_lines, float_literal, function_name, identifier, literal_value, operation, parameters,
+    CodegenOptions, FloatSyntax, SourceFile,
+};
+use crate::{read_fef::FefFile, written::Written};
+
+/// Keywords of C and C++
+const C_KEYWORDS: Set<&'static str> = phf_set!(
+    "alignas",
+    "alignof",
+    "and",
+    "and_eq",
+    "asm",
+    "auto",
+    "bitand",
+    "bitor",
+    "bool",
+    "break",
+    "case",
+    "catch",
+    "char",
+    "char16_t",
+    "char32_t",
+    "char8_t",
+    "class",
+    "co_await",
+    "co_return",
+    "co_yield",
+    "compl",
+    "concept",
+    "const",
+    "const_cast",
+    "consteval",
+    "constexpr",
+    "constinit",
+    "continue",
+    "decltype",
+    "default",
+    "delete",
+    "do",
+    "double",
+    "dynamic_cast",
+    "else",
+    "enum",
+    "explicit",
+    "export",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "friend",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "not",
+    "not_eq",
+    "nullptr",
+    "operator",
+    "or",
+    "or_eq",
+    "private",
+    "protected",
+    "public",
+    "register",
+    "reinterpret_cast",
+    "requires",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "static_cast",
+    "struct",
+    "switch",
+    "template",
+    "this",
+    "thread_local",
+    "throw",
+    "true",
+    "try",
+    "typedef",
+    "typeid",
+    "typename",
+    "typeof",
+    "typeof_unqual",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "wchar_t",
+    "while",
+    "xor",
+    "xor_eq",
+);
+
+/// The `<math.h>` functions and helper functions used by the generated code, which parameters would shadow
+const C_LIBRARY_NAMES: Set<&'static str> = phf_set!(
+    "cbrt",
+    "floor",
+    "fmod",
+    "pow",
+    "sqrt",
+    "fefy_square",
+    "fefy_cube",
+);
+
+/// Names identifiers of the generated code can't have
+const C_RESERVED: [&Set<&str>; 2] = [&C_KEYWORDS, &C_LIBRARY_NAMES];
+
+const C_FLOATS: FloatSyntax = FloatSyntax {
+    nan: "NAN",
+    infinity: "INFINITY",
+    suffix: "",
+    constant: |_| None,
+};
+
+/// Helper functions computing squares and cubes by multiplication, as the interpreter does
+const SQUARE_FUNCTION: &str = "static double fefy_square(double x)\n{\n    return x * x;\n}\n\n";
+const CUBE_FUNCTION: &str = "static double fefy_cube(double x)\n{\n    return x * x * x;\n}\n\n";
+
+/// A C header declaring a pure function computing the formula and a source file defining it
+///
+/// The function uses the same floating point operations as the interpreter, with the `<math.h>` functions
+/// `pow`, `cbrt`, `sqrt`, `floor` and `fmod`, and multiplication for squares and cubes.
+pub(super) fn to_c(
+    fef_file: &FefFile,
+    stem: Option<&str>,
+    options: &CodegenOptions,
+) -> Result<Vec<SourceFile>> {
+    if options.tests {
+        bail!("Tests can only be generated for Rust");
+    }
+
+    let function = c_function_name(fef_file)?;
+    let parameters = parameters(fef_file, &C_RESERVED)?;
+    if options.variables_struct && parameters.is_empty() {
+        bail!("A formula without variables has no struct of variables");
+    }
+    let stem = stem.unwrap_or(&function);
+
+    let mut writer = CWriter {
+        names: parameters
+            .iter()
+            .map(|parameter| (&parameter.identifier, parameter.name.as_str()))
+            .collect(),
+        used: BTreeSet::new(),
+        square: false,
+        cube: false,
+    };
+    let body = writer.code(&fef_file.expression)?.text;
+
+    let signature = format!(
+        "double {}({})",
+        function,
+        match parameters.is_empty() {
+            true => "void".to_string(),
+            false => parameters
+                .iter()
+                .map(|parameter| format!("double {}", parameter.name))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    );
+    let struct_name = format!("struct {}_variables", function);
+    let struct_signature = format!(
+        "double {}_struct(const {} *variables)",
+        function, struct_name
+    );
+
+    let guard = format!("{}_H", identifier(stem, &C_RESERVED).to_ascii_uppercase());
+    let mut header = comment(fef_file, &format!("{}.h", stem))?;
+    header.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+    header.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+    header.push_str(&format!("{};\n", signature));
+    if options.variables_struct {
+        header.push_str(&format!(
+            "\n/* Variables of {} */\n{} {{\n",
+            function, struct_name
+        ));
+        for parameter in &parameters {
+            header.push_str(&format!("    double {};\n", parameter.name));
+        }
+        header.push_str(&format!("}};\n\n{};\n", struct_signature));
+    }
+    header.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+    header.push_str(&format!("#endif /* {} */\n", guard));
+
+    let mut source = comment(fef_file, &format!("{}.c", stem))?;
+    source.push_str(&format!("#include \"{}.h\"\n\n#include <math.h>\n\n", stem));
+    if writer.square {
+        source.push_str(SQUARE_FUNCTION);
+    }
+    if writer.cube {
+        source.push_str(CUBE_FUNCTION);
+    }
+    source.push_str(&format!("{}\n{{\n", signature));
+    for parameter in &parameters {
+        if !writer.used.contains(&parameter.identifier) {
+            source.push_str(&format!("    (void){};\n", parameter.name));
+        }
+    }
+    source.push_str(&format!("    return {};\n}}\n", body));
+    if options.variables_struct {
+        source.push_str(&format!(
+            "\n{}\n{{\n    return {}({});\n}}\n",
+            struct_signature,
+            function,
+            parameters
+                .iter()
+                .map(|parameter| format!("variables->{}", parameter.name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    Ok(vec![
+        SourceFile {
+            extension: "h",
+            source: header,
+        },
+        SourceFile {
+            extension: "c",
+            source,
+        },
+    ])
+}
+
+/// Name of the generated function with the prefix `fefy_`, so that it can't clash with the functions of
+/// `<math.h>`, e.g. for a formula named `log`
+fn c_function_name(fef_file: &FefFile) -> Result<String> {
+    let name = function_name(fef_file, &C_RESERVED)?;
+    Ok(identifier(&format!("fefy_{}", name), &C_RESERVED))
+}
+
+/// The block comment a generated file starts with, with its file name, the name of the formula and the formula
+fn comment(fef_file: &FefFile, file_name: &str) -> Result<String> {
+    let formula = crate::decompile::decompile(&fef_file.expression, &fef_file.variable_names())?;
+
+    let mut text = format!("{}\n\n", file_name);
+    if let Some(name) = fef_file.name()? {
+        text.push_str(&format!("{}\n\n", name));
+    }
+    text.push_str("Generated by fefy from the formula\n\n");
+    text.push_str(&comment_lines(&formula, "    "));
+
+    let mut comment = String::from("/*");
+    for (index, line) in text.lines().enumerate() {
+        if index > 0 {
+            comment.push_str("\n *");
+        }
+        if !line.is_empty() {
+            // The comment would end at a `*/` in a name or the formula
+            comment.push(' ');
+            comment.push_str(&line.replace("*/", "* /"));
+        }
+    }
+    comment.push_str("\n */\n\n");
+    Ok(comment)
+}
+
+struct CWriter<'a> {
+    names: BTreeMap<&'a VariableLengthEnum, &'a str>,
+    /// Identifiers of the variables used in the expression
+    used: BTreeSet<VariableLengthEnum>,
+    /// Whether the expression uses the square helper function
+    square: bool,
+    /// Whether the expression uses the cube helper function
+    cube: bool,
+}
+
+impl CWriter<'_> {
+    fn code(&mut self, tree: &ExprTree) -> Result<Written> {
+        if let Some(value) = literal_value(tree) {
+            return Ok(float_literal(value, &C_FLOATS));
+        }
+        let code = match tree.inner() {
+            Expr::Variable(expr) => {
+                let identifier: VariableLengthEnum = expr.clone().into();
+                let name = self
+                    .names
+                    .get(&identifier)
+                    .expect("Every variable of the formula is a parameter")
+                    .to_string();
+                self.used.insert(identifier);
+                Written::atom(name)
+            }
+            Expr::Addition(expr) => self.binary(expr, "+")?,
+            Expr::Subtraction(expr) => self.binary(expr, "-")?,
+            Expr::Multiplication(expr) => self.binary(expr, "*")?,
+            Expr::Division(expr) => self.binary(expr, "/")?,
+            Expr::Modulo(expr) => self.call("fmod", &[expr.lhs(), expr.rhs()])?,
+            Expr::IntDivision(expr) => {
+                let quotient = self.binary(expr, "/")?;
+                Written::atom(format!("floor({})", quotient.text))
+            }
+            Expr::Power(expr) => self.call("pow", &[expr.lhs(), expr.rhs()])?,
+            Expr::Root(expr) => {
+                let power = self.root(expr.lhs(), expr.rhs())?;
+                Written::atom(power)
+            }
+            Expr::IntRoot(expr) => {
+                let power = self.root(expr.lhs(), expr.rhs())?;
+                Written::atom(format!("floor({})", power))
+            }
+            Expr::Negation(expr) => operation(format!("-{}", self.code(expr.inner())?.operand())),
+            Expr::Square(expr) => {
+                self.square = true;
+                self.call("fefy_square", &[expr.inner()])?
+            }
+            Expr::Cube(expr) => {
+                self.cube = true;
+                self.call("fefy_cube", &[expr.inner()])?
+            }
+            Expr::SquareRoot(expr) => self.call("sqrt", &[expr.inner()])?,
+            Expr::CubeRoot(expr) => self.call("cbrt", &[expr.inner()])?,
+            Expr::Reciprocal(expr) => {
+                operation(format!("1.0 / {}", self.code(expr.inner())?.operand()))
+            }
+            expr => bail!("Expression {} can't be generated as C", expr.token()),
+        };
+        Ok(code)
+    }
+
+    fn binary(
+        &mut self,
+        expr: &impl BinaryOperationExpr<ExprTree>,
+        operator: &str,
+    ) -> Result<Written> {
+        let lhs = self.code(expr.lhs())?;
+        let rhs = self.code(expr.rhs())?;
+        Ok(operation(format!(
+            "{} {} {}",
+            lhs.operand(),
+            operator,
+            rhs.operand()
+        )))
+    }
+
+    fn call(&mut self, function: &str, arguments: &[&ExprTree]) -> Result<Written> {
+        let arguments = arguments
+            .iter()
+            .map(|argument| Ok(self.code(argument)?.text))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Written::atom(format!(
+            "{}({})",
+            function,
+            arguments.join(", ")
+        )))
+    }
+
+    /// The root as a power with the reciprocal of the degree, like the interpreter computes it
+    fn root(&mut self, degree: &ExprTree, base: &ExprTree) -> Result<String> {
+        let degree = self.code(degree)?;
+        let base = self.code(base)?;
+        Ok(format!("pow({}, 1.0 / {})", base.text, degree.operand()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_c;
+    use crate::{codegen::CodegenOptions, read_fef::FefFile};
+
+    /// The header and the source file
+    fn c(formula: &str, stem: Option<&str>, options: &CodegenOptions) -> (String, String) {
+        let files = to_c(&FefFile::parse("Test", formula), stem, options).expect("Generates");
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].extension, "h");
+        assert_eq!(files[1].extension, "c");
+        (files[0].source.clone(), files[1].source.clone())
+    }
+
+    /// The expression returned by the function computing the formula, the last function of the source file
+    fn body(formula: &str) -> String {
+        let (_, source) = c(formula, None, &CodegenOptions::default());
+        let start = source.rfind("    return ").expect("Has a function") + "    return ".len();
+        let end = start + source[start..].find(";\n").expect("Has a body");
+        source[start..end].to_string()
+    }
+
+    #[test]
+    fn header_and_source_with_struct() {
+        let fef_file = FefFile::parse("Kinetic energy", "m * v^2 / 2");
+        let options = CodegenOptions {
+            variables_struct: true,
+            ..Default::default()
+        };
+        let files = to_c(&fef_file, None, &options).unwrap();
+        assert_eq!(
+            files[0].source,
+            r#"/* fefy_kinetic_energy.h
+ *
+ * Kinetic energy
+ *
+ * Generated by fefy from the formula
+ *
+ *     m * v^2 / 2
+ */
+
+#ifndef FEFY_KINETIC_ENERGY_H
+#define FEFY_KINETIC_ENERGY_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+double fefy_kinetic_energy(double m, double v);
+
+/* Variables of fefy_kinetic_energy */
+struct fefy_kinetic_energy_variables {
+    double m;
+    double v;
+};
+
+double fefy_kinetic_energy_struct(const struct fefy_kinetic_energy_variables *variables);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FEFY_KINETIC_ENERGY_H */
+"#
+        );
+        assert_eq!(
+            files[1].source,
+            r#"/* fefy_kinetic_energy.c
+ *
+ * Kinetic energy
+ *
+ * Generated by fefy from the formula
+ *
+ *     m * v^2 / 2
+ */
+
+#include "fefy_kinetic_energy.h"
+
+#include <math.h>
+
+double fefy_kinetic_energy(double m, double v)
+{
+    return (m * pow(v, 2.0)) / 2.0;
+}
+
+double fefy_kinetic_energy_struct(const struct fefy_kinetic_energy_variables *variables)
+{
+    return fefy_kinetic_energy(variables->m, variables->v);
+}
+"#
+        );
+    }
+
+    #[test]
+    fn files_are_named_by_the_stem() {
+        let (header, source) = c("x", Some("formula"), &CodegenOptions::default());
+        assert!(header.starts_with("/* formula.h\n"));
+        assert!(header.contains("#ifndef FORMULA_H\n#define FORMULA_H\n"));
+        assert!(header.contains("double fefy_test(double x);\n"));
+        assert!(source.starts_with("/* formula.c\n"));
+        assert!(source.contains("#include \"formula.h\"\n"));
+    }
+
+    #[test]
+    fn squares_and_cubes_use_helper_functions() {
+        let (_, source) = c("sq(x) + cube(x + 1)", None, &CodegenOptions::default());
+        assert!(source.contains(
+            "static double fefy_square(double x)\n{\n    return x * x;\n}\n\nstatic double fefy_cube(double x)\n{\n    return x * x * x;\n}\n\ndouble fefy_test(double x)\n"
+        ));
+        assert!(!c("x", None, &CodegenOptions::default())
+            .1
+            .contains("fefy_square"));
+    }
+
+    #[test]
+    fn operations() {
+        assert_eq!(
+            body("-(a + b)^2 / (c - -d) + sqrt(x) * cbrt(y) - root(3, z) % 2"),
+            "(((-pow(a + b, 2.0)) / (c - (-d))) + (sqrt(x) * cbrt(y))) - fmod(pow(z, 1.0 / 3.0), 2.0)"
+        );
+        assert_eq!(
+            body("sq(x) + pi + 1e300 + x // 2 + 0.0 / 0"),
+            "(((fefy_square(x) + 3.141592653589793) + 1e300) + floor(x / 2.0)) + (0.0 / 0.0)"
+        );
+    }
+
+    #[test]
+    fn reserved_names_are_not_parameter_names() {
+        let (header, _) = c(
+            "double + pow + \"my var\"",
+            None,
+            &CodegenOptions::default(),
+        );
+        assert!(header.contains("double fefy_test(double double_, double pow_, double my_var);\n"));
+        assert_eq!(
+            body("double + pow + \"my var\""),
+            "(double_ + pow_) + my_var"
+        );
+    }
+
+    #[test]
+    fn formula_without_variables() {
+        let (header, source) = c("2", None, &CodegenOptions::default());
+        assert!(header.contains("double fefy_test(void);\n"));
+        assert!(source.contains("double fefy_test(void)\n{\n    return 2.0;\n}\n"));
+        let options = CodegenOptions {
+            variables_struct: true,
+            ..Default::default()
+        };
+        assert!(to_c(&FefFile::parse("Test", "2"), None, &options).is_err());
+    }
+
+    #[test]
+    fn tests_are_rejected() {
+        let options = CodegenOptions {
+            tests: true,
+            ..Default::default()
+        };
+        assert!(to_c(&FefFile::parse("Test", "x"), None, &options).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+mod c;
+mod rust;
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use fef::v0::{
+    expr::{Expr, ExprTree},
+    raw::VariableLengthEnum,
+};
+use phf::Set;
+
+use crate::{compose_tree::infallible, constants::Constant, read_fef::FefFile, written::Written};
+
+/// Programming language source code is generated in
+#[derive(Debug, PartialEq, Eq, Clone, Copy, clap::ValueEnum)]
+pub(crate) enum Language {
+    /// A Rust function taking the variables as `f64` parameters
+    Rust,
+    /// A C header and source file with a function taking the variables as `double` parameters
+    C,
+}
+
+/// What is generated besides the function computing the formula
+#[derive(Debug, Default, Clone)]
+pub(crate) struct CodegenOptions {
+    /// Generate tests checking the function against the interpreter
+    pub(crate) tests: bool,
+    /// Generate a variant of the function taking a struct of the variables
+    pub(crate) variables_struct: bool,
+}
+
+/// A generated source file
+pub(crate) struct SourceFile {
+    /// Extension of the file, the files of a formula differ only in their extensions
+    pub(crate) extension: &'static str,
+    pub(crate) source: String,
+}
+
+/// Source files with a function computing the formula of a FEF file
+///
+/// The stem is the file name the files are written to without its extension, which C sources use to
+/// include their header. The name of the function is used if it is not given.
+pub(crate) fn codegen(
+    fef_file: &FefFile,
+    language: Language,
+    stem: Option<&str>,
+    options: &CodegenOptions,
+) -> Result<Vec<SourceFile>> {
+    match language {
+        Language::Rust => rust::to_rust(fef_file, options),
+        Language::C => c::to_c(fef_file, stem, options),
+    }
+}
+
+/// Code of an operation, which is parenthesised as an operand of any other operation
+fn operation(text: String) -> Written {
+    Written::compound(text, 0)
+}
+
+/// How a language writes 64-bit float literals
+struct FloatSyntax {
+    nan: &'static str,
+    infinity: &'static str,
+    /// Suffix of literals of finite values
+    suffix: &'static str,
+    /// The name of the standard constant with the value of the built-in constant, if the language has one
+    constant: fn(Constant) -> Option<&'static str>,
+}
+
+/// A float literal with exactly the value, using the standard constants of the language where they match
+fn float_literal(value: f64, syntax: &FloatSyntax) -> Written {
+    if value.is_nan() {
+        return Written::atom(syntax.nan.to_string());
+    }
+    let magnitude = match value.abs() {
+        magnitude if magnitude.is_infinite() => syntax.infinity.to_string(),
+        magnitude => match Constant::from_value(magnitude).and_then(syntax.constant) {
+            Some(constant) => constant.to_string(),
+            // The shortest form that reads back as the same value
+            None => format!("{:?}{}", magnitude, syntax.suffix),
+        },
+    };
+    if value.is_sign_negative() {
+        operation(format!("-{}", magnitude))
+    } else {
+        Written::atom(magnitude)
+    }
+}
+
+/// The value the interpreter computes with for a number or boolean literal, `None` for other expressions
+fn literal_value(tree: &ExprTree) -> Option<f64> {
+    Some(match tree.inner() {
+        Expr::SignedIntLiteral(expr) => infallible::<i64>(expr.clone().try_into()) as f64,
+        Expr::UnsignedIntLiteral(expr) => infallible::<u64>(expr.clone().try_into()) as f64,
+        Expr::BinaryFloat32Literal(expr) => f64::from(infallible::<f32>(expr.clone().try_into())),
+        Expr::BinaryFloat64Literal(expr) => infallible(expr.clone().try_into()),
+        Expr::TrueLiteral(_) => 1.0,
+        Expr::FalseLiteral(_) => 0.0,
+        _ => return None,
+    })
+}
+
+/// A variable of the formula as a parameter of the generated function
+struct Parameter {
+    identifier: VariableLengthEnum,
+    name: String,
+}
+
+/// Parameters for all variables of the formula in the order of their identifiers, with distinct names that
+/// are valid identifiers of the language
+fn parameters(fef_file: &FefFile, reserved: &[&Set<&str>]) -> Result<Vec<Parameter>> {
+    let mut used = BTreeSet::new();
+    Ok(fef_file
+        .variables()?
+        .into_iter()
+        .map(|variable| {
+            let mut name = variable
+                .name
+                .map(|name| identifier(name, reserved))
+                .unwrap_or_default();
+            if name.is_empty() {
+                name = format!("x{}", variable.identifier);
+            }
+            if used.contains(&name) {
+                name = format!("{}_{}", name, variable.identifier);
+            }
+            used.insert(name.clone());
+            Parameter {
+                identifier: variable.identifier,
+                name,
+            }
+        })
+        .collect())
+}
+
+/// Name of the generated function, derived from the name of the formula
+fn function_name(fef_file: &FefFile, reserved: &[&Set<&str>]) -> Result<String> {
+    let name = fef_file
+        .name()?
+        .map(|name| identifier(name, reserved))
+        .unwrap_or_default();
+    Ok(if name.is_empty() {
+        "formula".to_string()
+    } else {
+        name
+    })
+}
+
+/// The text in snake case with only ASCII letters, digits and underscores, empty if nothing is left
+///
+/// Other characters separate words, e.g. `Flow rate [m3/s]` becomes `flow_rate_m3_s`. A leading digit is
+/// preceded by an underscore and names in any of the reserved sets, such as keywords, are followed by one.
+fn identifier(text: &str, reserved: &[&Set<&str>]) -> String {
+    let mut identifier = String::new();
+    let mut previous: Option<char> = None;
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            let word_start = c.is_ascii_uppercase()
+                && previous.is_some_and(|previous| previous.is_ascii_lowercase());
+            if word_start || (previous.is_none() && !identifier.is_empty()) {
+                identifier.push('_');
+            }
+            identifier.push(c.to_ascii_lowercase());
+            previous = Some(c);
+        } else {
+            previous = None;
+        }
+    }
+
+    if identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    if reserved
+        .iter()
+        .any(|names| names.contains(identifier.as_str()))
+    {
+        identifier.push('_');
+    }
+    identifier
+}
+
+/// The text in line comments with the prefix, one for each of its lines
+fn comment_lines(text: &str, prefix: &str) -> String {
+    text.lines()
+        .map(|line| format!("{}\n", format!("{}{}", prefix, line).trim_end()))
+        .collect()
+}
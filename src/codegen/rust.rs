@@ -0,0 +1,358 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{bail, Result};
+use fef::v0::{
+    expr::{
+        traits::{BinaryOperationExpr, ExprObj, UnaryOperationExpr},
+        Expr, ExprTree,
+    },
+    raw::VariableLengthEnum,
+};
+use phf::{phf_set, Set};
+
+use super::{
+    comment_lines, float_literal, function_name, literal_value, operation, parameters,
+    CodegenOptions, FloatSyntax, Parameter, SourceFile,
+};
+use crate::{constants::Constant, read_fef::FefFile, written::Written};
+
+const RUST_KEYWORDS: Set<&'static str> = phf_set!(
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+    "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+    "use", "where", "while", "abstract", "become", "box", "do", "final", "macro", "override",
+    "priv", "try", "typeof", "unsized", "virtual", "yield",
+);
+
+const RUST_FLOATS: FloatSyntax = FloatSyntax {
+    nan: "f64::NAN",
+    infinity: "f64::INFINITY",
+    suffix: "_f64",
+    constant: rust_constant,
+};
+
+/// Values the variables take in the generated tests, chosen to avoid integer results only
+const SAMPLE_VALUES: [f64; 7] = [0.5, 2.0, 3.0, -1.5, 10.0, 0.25, 7.0];
+
+/// Number of calls checked by the generated tests of a function with parameters
+const SAMPLE_COUNT: usize = 5;
+
+/// Source of a Rust function computing the formula, with the same floating point operations as the interpreter
+pub(super) fn to_rust(fef_file: &FefFile, options: &CodegenOptions) -> Result<Vec<SourceFile>> {
+    if options.variables_struct {
+        bail!("A struct of variables can only be generated for C");
+    }
+    let function = function_name(fef_file, &[&RUST_KEYWORDS])?;
+    let parameters = parameters(fef_file, &[&RUST_KEYWORDS])?;
+
+    let mut writer = RustWriter {
+        names: parameters
+            .iter()
+            .map(|parameter| (&parameter.identifier, parameter.name.as_str()))
+            .collect(),
+        used: BTreeSet::new(),
+    };
+    let body = writer.code(&fef_file.expression)?.text;
+    let formula = crate::decompile::decompile(&fef_file.expression, &fef_file.variable_names())?;
+
+    let mut source = String::new();
+    if let Some(name) = fef_file.name()? {
+        source.push_str(&comment_lines(name, "/// "));
+        source.push_str("///\n");
+    }
+    source.push_str("/// Generated by fefy from the formula\n///\n/// ```text\n");
+    source.push_str(&comment_lines(&formula, "/// "));
+    source.push_str("/// ```\n");
+    if parameters
+        .iter()
+        .any(|parameter| !writer.used.contains(&parameter.identifier))
+    {
+        source.push_str("#[allow(unused_variables)]\n");
+    }
+    source.push_str(&format!(
+        "pub fn {}({}) -> f64 {{\n    {}\n}}\n",
+        function,
+        parameters
+            .iter()
+            .map(|parameter| format!("{}: f64", parameter.name))
+            .collect::<Vec<_>>()
+            .join(", "),
+        body
+    ));
+
+    if options.tests {
+        source.push('\n');
+        source.push_str(&tests(fef_file, &function, &parameters)?);
+    }
+    Ok(vec![SourceFile {
+        extension: "rs",
+        source,
+    }])
+}
+
+/// A test module comparing the function to values computed by the interpreter for sample arguments
+fn tests(fef_file: &FefFile, function: &str, parameters: &[Parameter]) -> Result<String> {
+    let sample_count = if parameters.is_empty() {
+        1
+    } else {
+        SAMPLE_COUNT
+    };
+
+    let mut assertions = String::new();
+    for sample in 0..sample_count {
+        let arguments: Vec<f64> = (0..parameters.len())
+            .map(|index| SAMPLE_VALUES[(sample + 2 * index) % SAMPLE_VALUES.len()])
+            .collect();
+        let variable_values = parameters
+            .iter()
+            .map(|parameter| parameter.identifier.clone())
+            .zip(arguments.iter().copied())
+            .collect();
+        let expected = crate::evaluate_fef_stream::evaluate_tree_as_fef_expr(
+            &fef_file.expression,
+            variable_values,
+        )?;
+
+        assertions.push_str(&format!(
+            "        assert_same({}({}), {});\n",
+            function,
+            arguments
+                .iter()
+                .map(|argument| float_literal(*argument, &RUST_FLOATS).text)
+                .collect::<Vec<_>>()
+                .join(", "),
+            float_literal(expected, &RUST_FLOATS).text
+        ));
+    }
+
+    Ok(format!(
+        concat!(
+            "#[cfg(test)]\n",
+            "mod {}_tests {{\n",
+            "    use super::*;\n",
+            "\n",
+            "    /// Asserts the values are the same, bit for bit or both NaN\n",
+            "    fn assert_same(actual: f64, expected: f64) {{\n",
+            "        assert!(\n",
+            "            actual.to_bits() == expected.to_bits() || (actual.is_nan() && expected.is_nan()),\n",
+            "            \"{{}} != {{}}\",\n",
+            "            actual,\n",
+            "            expected\n",
+            "        );\n",
+            "    }}\n",
+            "\n",
+            "    #[test]\n",
+            "    fn matches_interpreter() {{\n",
+            "        // Expected values computed by the fefy interpreter\n",
+            "{}",
+            "    }}\n",
+            "}}\n",
+        ),
+        function.trim_end_matches('_'),
+        assertions
+    ))
+}
+
+struct RustWriter<'a> {
+    names: BTreeMap<&'a VariableLengthEnum, &'a str>,
+    /// Identifiers of the variables used in the expression
+    used: BTreeSet<VariableLengthEnum>,
+}
+
+impl RustWriter<'_> {
+    fn code(&mut self, tree: &ExprTree) -> Result<Written> {
+        if let Some(value) = literal_value(tree) {
+            return Ok(float_literal(value, &RUST_FLOATS));
+        }
+        let code = match tree.inner() {
+            Expr::Variable(expr) => {
+                let identifier: VariableLengthEnum = expr.clone().into();
+                let name = self
+                    .names
+                    .get(&identifier)
+                    .expect("Every variable of the formula is a parameter")
+                    .to_string();
+                self.used.insert(identifier);
+                Written::atom(name)
+            }
+            Expr::Addition(expr) => self.binary(expr, "+")?,
+            Expr::Subtraction(expr) => self.binary(expr, "-")?,
+            Expr::Multiplication(expr) => self.binary(expr, "*")?,
+            Expr::Division(expr) => self.binary(expr, "/")?,
+            Expr::Modulo(expr) => self.binary(expr, "%")?,
+            Expr::IntDivision(expr) => {
+                let quotient = self.binary(expr, "/")?;
+                Written::atom(format!("{}.floor()", quotient.operand()))
+            }
+            Expr::Power(expr) => {
+                let base = self.code(expr.lhs())?;
+                let exponent = self.code(expr.rhs())?;
+                Written::atom(format!("{}.powf({})", base.operand(), exponent.text))
+            }
+            Expr::Root(expr) => {
+                let degree = self.code(expr.lhs())?;
+                let base = self.code(expr.rhs())?;
+                Written::atom(format!(
+                    "{}.powf(1.0_f64 / {})",
+                    base.operand(),
+                    degree.operand()
+                ))
+            }
+            Expr::IntRoot(expr) => {
+                let degree = self.code(expr.lhs())?;
+                let base = self.code(expr.rhs())?;
+                Written::atom(format!(
+                    "{}.powf(1.0_f64 / {}).floor()",
+                    base.operand(),
+                    degree.operand()
+                ))
+            }
+            Expr::Negation(expr) => operation(format!("-{}", self.code(expr.inner())?.operand())),
+            Expr::Square(expr) => self.method(expr.inner(), "powi(2)")?,
+            Expr::Cube(expr) => self.method(expr.inner(), "powi(3)")?,
+            Expr::SquareRoot(expr) => self.method(expr.inner(), "sqrt()")?,
+            Expr::CubeRoot(expr) => self.method(expr.inner(), "cbrt()")?,
+            Expr::Reciprocal(expr) => {
+                operation(format!("1.0_f64 / {}", self.code(expr.inner())?.operand()))
+            }
+            expr => bail!("Expression {} can't be generated as Rust", expr.token()),
+        };
+        Ok(code)
+    }
+
+    fn binary(
+        &mut self,
+        expr: &impl BinaryOperationExpr<ExprTree>,
+        operator: &str,
+    ) -> Result<Written> {
+        let lhs = self.code(expr.lhs())?;
+        let rhs = self.code(expr.rhs())?;
+        Ok(operation(format!(
+            "{} {} {}",
+            lhs.operand(),
+            operator,
+            rhs.operand()
+        )))
+    }
+
+    fn method(&mut self, receiver: &ExprTree, call: &str) -> Result<Written> {
+        let receiver = self.code(receiver)?;
+        Ok(Written::atom(format!("{}.{}", receiver.operand(), call)))
+    }
+}
+
+/// The constant of `std::f64::consts` with the value of the built-in constant
+fn rust_constant(constant: Constant) -> Option<&'static str> {
+    match constant {
+        Constant::Pi => Some("std::f64::consts::PI"),
+        Constant::E => Some("std::f64::consts::E"),
+        Constant::Tau => Some("std::f64::consts::TAU"),
+        Constant::Phi => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_rust;
+    use crate::{codegen::CodegenOptions, read_fef::FefFile};
+
+    fn rust(name: &str, formula: &str, options: &CodegenOptions) -> String {
+        let files = to_rust(&FefFile::parse(name, formula), options).expect("Generates");
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension, "rs");
+        files[0].source.clone()
+    }
+
+    /// The line of the function body computing the formula
+    fn body(formula: &str) -> String {
+        let source = rust("Test", formula, &CodegenOptions::default());
+        let start = source.find("-> f64 {\n").expect("Has a function") + "-> f64 {\n".len();
+        let end = start + source[start..].find('\n').expect("Has a body");
+        source[start..end].trim().to_string()
+    }
+
+    #[test]
+    fn function_with_tests() {
+        assert_eq!(
+            rust(
+                "Kinetic energy",
+                "m * v^2 / 2",
+                &CodegenOptions {
+                    tests: true,
+                    ..Default::default()
+                }
+            ),
+            r#"/// Kinetic energy
+///
+/// Generated by fefy from the formula
+///
+/// ```text
+/// m * v^2 / 2
+/// ```
+pub fn kinetic_energy(m: f64, v: f64) -> f64 {
+    (m * v.powf(2.0_f64)) / 2.0_f64
+}
+
+#[cfg(test)]
+mod kinetic_energy_tests {
+    use super::*;
+
+    /// Asserts the values are the same, bit for bit or both NaN
+    fn assert_same(actual: f64, expected: f64) {
+        assert!(
+            actual.to_bits() == expected.to_bits() || (actual.is_nan() && expected.is_nan()),
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn matches_interpreter() {
+        // Expected values computed by the fefy interpreter
+        assert_same(kinetic_energy(0.5_f64, 3.0_f64), 2.25_f64);
+        assert_same(kinetic_energy(2.0_f64, -1.5_f64), 2.25_f64);
+        assert_same(kinetic_energy(3.0_f64, 10.0_f64), 150.0_f64);
+        assert_same(kinetic_energy(-1.5_f64, 0.25_f64), -0.046875_f64);
+        assert_same(kinetic_energy(10.0_f64, 7.0_f64), 245.0_f64);
+    }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn operations() {
+        assert_eq!(
+            body("-(a + b)^2 / (c - -d) + sqrt(x) * cbrt(y) - root(3, z) % 2"),
+            "(((-(a + b).powf(2.0_f64)) / (c - (-d))) + (x.sqrt() * y.cbrt())) - (z.powf(1.0_f64 / 3.0_f64) % 2.0_f64)"
+        );
+        assert_eq!(
+            body("x // 2 + introot(3, x) + (-x)^2"),
+            "((x / 2.0_f64).floor() + x.powf(1.0_f64 / 3.0_f64).floor()) + (-x).powf(2.0_f64)"
+        );
+    }
+
+    #[test]
+    fn literals_and_constants() {
+        assert_eq!(
+            body("pi * e + tau + 1e300 + 5i64 + -0.0"),
+            "((((std::f64::consts::PI * std::f64::consts::E) + std::f64::consts::TAU) + 1e300_f64) + 5.0_f64) + (-0.0_f64)"
+        );
+    }
+
+    #[test]
+    fn keywords_are_not_parameter_names() {
+        assert!(rust("Test", "type + self + \"my var\"", &CodegenOptions::default())
+            .contains("pub fn test(type_: f64, self_: f64, my_var: f64) -> f64 {\n    (type_ + self_) + my_var\n}"));
+    }
+
+    #[test]
+    fn variables_struct_is_rejected() {
+        let options = CodegenOptions {
+            variables_struct: true,
+            ..Default::default()
+        };
+        assert!(to_rust(&FefFile::parse("Test", "x"), &options).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use std::convert::Infallible;
+
+use fef::v0::{
+    expr::{
+        error::{ComposeError, DefaultComposeError},
+        traits::{BinaryOperationExpr, Composer, UnaryOperationExpr},
+        Expr, ExprTree,
+    },
+    raw::VariableLengthEnum,
+};
+
+/// Composes an in-memory expression tree with a composer, the same way reading it from a stream would
+///
+/// Children are composed before their parents, so the composer sees exactly the calls it would see
+/// from [`fef::v0::read::read_expression`].
+pub(crate) fn compose_tree<S, CP: Composer<S>>(
+    tree: &ExprTree,
+    composer: &mut CP,
+) -> Result<S, ComposeError<CP::Error>> {
+    match tree.inner() {
+        Expr::Variable(expr) => {
+            let identifier: VariableLengthEnum = expr.clone().into();
+            composer.compose_variable(identifier.into())
+        }
+        Expr::SignedIntLiteral(expr) => {
+            let value: i64 = infallible(expr.clone().try_into());
+            composer.compose_signed_int_literal(value.into())
+        }
+        Expr::UnsignedIntLiteral(expr) => {
+            let value: u64 = infallible(expr.clone().try_into());
+            composer.compose_unsigned_int_literal(value.into())
+        }
+        Expr::BinaryFloat32Literal(expr) => {
+            let value: f32 = infallible(expr.clone().try_into());
+            composer.compose_binary_float_32_literal(value.into())
+        }
+        Expr::BinaryFloat64Literal(expr) => {
+            let value: f64 = infallible(expr.clone().try_into());
+            composer.compose_binary_float_64_literal(value.into())
+        }
+        Expr::TrueLiteral(_) => composer.compose_true_literal(().into()),
+        Expr::FalseLiteral(_) => composer.compose_false_literal(().into()),
+        Expr::Addition(expr) => {
+            let operands = compose_operands(expr, composer)?;
+            composer.compose_addition(operands.into())
+        }
+        Expr::Subtraction(expr) => {
+            let operands = compose_operands(expr, composer)?;
+            composer.compose_subtraction(operands.into())
+        }
+        Expr::Multiplication(expr) => {
+            let operands = compose_operands(expr, composer)?;
+            composer.compose_multiplication(operands.into())
+        }
+        Expr::Division(expr) => {
+            let operands = compose_operands(expr, composer)?;
+            composer.compose_division(operands.into())
+        }
+        Expr::IntDivision(expr) => {
+            let operands = compose_operands(expr, composer)?;
+            composer.compose_int_division(operands.into())
+        }
+        Expr::Modulo(expr) => {
+            let operands = compose_operands(expr, composer)?;
+            composer.compose_modulo(operands.into())
+        }
+        Expr::Power(expr) => {
+            let operands = compose_operands(expr, composer)?;
+            composer.compose_power(operands.into())
+        }
+        Expr::Root(expr) => {
+            let operands = compose_operands(expr, composer)?;
+            composer.compose_root(operands.into())
+        }
+        Expr::IntRoot(expr) => {
+            let operands = compose_operands(expr, composer)?;
+            composer.compose_int_root(operands.into())
+        }
+        Expr::Negation(expr) => {
+            let operand = compose_tree(expr.inner(), composer)?;
+            composer.compose_negation(operand.into())
+        }
+        Expr::Square(expr) => {
+            let operand = compose_tree(expr.inner(), composer)?;
+            composer.compose_square(operand.into())
+        }
+        Expr::Cube(expr) => {
+            let operand = compose_tree(expr.inner(), composer)?;
+            composer.compose_cube(operand.into())
+        }
+        Expr::SquareRoot(expr) => {
+            let operand = compose_tree(expr.inner(), composer)?;
+            composer.compose_square_root(operand.into())
+        }
+        Expr::CubeRoot(expr) => {
+            let operand = compose_tree(expr.inner(), composer)?;
+            composer.compose_cube_root(operand.into())
+        }
+        Expr::Reciprocal(expr) => {
+            let operand = compose_tree(expr.inner(), composer)?;
+            composer.compose_reciprocal(operand.into())
+        }
+        _ => Err(DefaultComposeError::ComposeNotImplemented.into()),
+    }
+}
+
+fn compose_operands<S, CP: Composer<S>>(
+    expr: &impl BinaryOperationExpr<ExprTree>,
+    composer: &mut CP,
+) -> Result<(S, S), ComposeError<CP::Error>> {
+    let lhs = compose_tree(expr.lhs(), composer)?;
+    let rhs = compose_tree(expr.rhs(), composer)?;
+    Ok((lhs, rhs))
+}
+
+/// Unwraps the result of a conversion that can't fail
+pub(crate) fn infallible<T>(result: Result<T, Infallible>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(error) => match error {},
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt::Display;
+
+use fef::v0::expr::{Expr, ExprBinaryFloat64Literal, ExprTree};
+use phf::{phf_map, Map};
+
+/// Mathematical constants usable by name in the expression language, each written as a 64-bit float literal
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Constant {
+    Pi,
+    E,
+    Tau,
+    Phi,
+}
+
+const CONSTANT_MAP: Map<&'static str, Constant> = phf_map!(
+    "pi" => Constant::Pi,
+    "e" => Constant::E,
+    "tau" => Constant::Tau,
+    "phi" => Constant::Phi,
+);
+
+impl Constant {
+    pub(crate) fn from_name(name: &str) -> Option<Constant> {
+        CONSTANT_MAP.get(name).copied()
+    }
+
+    /// The constant with exactly the value
+    pub(crate) fn from_value(value: f64) -> Option<Constant> {
+        CONSTANT_MAP
+            .values()
+            .copied()
+            .find(|constant| constant.value() == value)
+    }
+
+    /// The name of the constant in the expression language
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Constant::Pi => "pi",
+            Constant::E => "e",
+            Constant::Tau => "tau",
+            Constant::Phi => "phi",
+        }
+    }
+
+    /// The closest f64 to the value of the constant
+    pub(crate) fn value(&self) -> f64 {
+        match self {
+            Constant::Pi => std::f64::consts::PI,
+            Constant::E => std::f64::consts::E,
+            Constant::Tau => std::f64::consts::TAU,
+            Constant::Phi => 1.618_033_988_749_895,
+        }
+    }
+
+    /// The constant as a 64-bit float literal
+    pub(crate) fn compose(&self) -> ExprTree {
+        let expr: Expr<ExprTree> = ExprBinaryFloat64Literal::from(self.value()).into();
+        expr.into()
+    }
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,407 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::Infallible,
+};
+
+use anyhow::{bail, Context, Result};
+use fef::v0::{
+    expr::{
+        error::ComposeError,
+        traits::{BinaryOperationExpr, Composer, ExprObj, UnaryOperationExpr},
+        Expr, ExprTree, ExprVariable,
+    },
+    raw::VariableLengthEnum,
+};
+
+use crate::{
+    compose_tree::{compose_tree, infallible},
+    constants::Constant,
+    functions::Function,
+    into_expr_tree::{Associativity, Operator},
+    parser::{quote_identifier, written_identifier},
+    written::Written,
+};
+
+/// Writes an expression tree in the expression language, with the fewest parentheses needed
+///
+/// Variables are written with their names, quoted where needed, unnamed variables get a placeholder name.
+/// 64-bit float literals with the value of a built-in constant are written as the constant. The variables
+/// are declared on a `var` line first when their identifiers aren't in the order of their first use.
+/// Creating a formula from the output with the default options gives the same tree: 32-bit float literals are
+/// written with their exact value, literals the default encoding would read back differently get a suffix,
+/// e.g. `5i64` or `0.5f64`, and negated number literals are parenthesised, e.g. `-(5)`.
+pub(crate) fn decompile(
+    tree: &ExprTree,
+    variable_names: &[(&VariableLengthEnum, &str)],
+) -> Result<String> {
+    let decompiler = Decompiler::new(variable_names);
+    let text = decompiler.decompile(tree)?.text;
+    Ok(match decompiler.declaration(tree)? {
+        Some(declaration) => format!("{}\n{}", declaration, text),
+        None => text,
+    })
+}
+
+struct Decompiler<'a> {
+    variable_names: BTreeMap<&'a VariableLengthEnum, &'a str>,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(variable_names: &[(&'a VariableLengthEnum, &'a str)]) -> Self {
+        Self {
+            variable_names: variable_names.iter().copied().collect(),
+        }
+    }
+
+    fn decompile(&self, tree: &ExprTree) -> Result<Written> {
+        let written = match tree.inner() {
+            Expr::Variable(expr) => {
+                let identifier: VariableLengthEnum = expr.clone().into();
+                Written::atom(self.variable_name(&identifier))
+            }
+            Expr::SignedIntLiteral(expr) => {
+                let value: i64 = infallible(expr.clone().try_into());
+                // Small non-negative integers are read back as unsigned literals
+                if (0..=i64::from(u32::MAX)).contains(&value) {
+                    Written::number(format!("{}i64", value))
+                } else {
+                    Written::number(value.to_string())
+                }
+            }
+            Expr::UnsignedIntLiteral(expr) => {
+                let value: u64 = infallible(expr.clone().try_into());
+                Written::number(value.to_string())
+            }
+            Expr::BinaryFloat32Literal(expr) => {
+                let value: f32 = infallible(expr.clone().try_into());
+                if !value.is_finite() {
+                    bail!(
+                        "Float literal {} can't be written in the expression language",
+                        value
+                    );
+                }
+                Written::number(float_text(f64::from(value)))
+            }
+            Expr::BinaryFloat64Literal(expr) => {
+                let value: f64 = infallible(expr.clone().try_into());
+                if !value.is_finite() {
+                    bail!(
+                        "Float literal {} can't be written in the expression language",
+                        value
+                    );
+                }
+                match Constant::from_value(value.abs()) {
+                    Some(constant) if value < 0.0 => Written::number(format!("-{}", constant)),
+                    Some(constant) => Written::number(constant.to_string()),
+                    // Values a 32-bit float holds exactly are read back as 32-bit floats
+                    None if f64::from(value as f32) == value => {
+                        Written::number(format!("{}f64", float_text(value)))
+                    }
+                    None => Written::number(float_text(value)),
+                }
+            }
+            Expr::TrueLiteral(_) => Written::atom("true".to_string()),
+            Expr::FalseLiteral(_) => Written::atom("false".to_string()),
+            Expr::Addition(expr) => self.binary(expr, Operator::Plus)?,
+            Expr::Subtraction(expr) => self.binary(expr, Operator::Minus)?,
+            Expr::Multiplication(expr) => self.binary(expr, Operator::Asterisk)?,
+            Expr::Division(expr) => self.binary(expr, Operator::Slash)?,
+            Expr::IntDivision(expr) => self.binary(expr, Operator::DoubleSlash)?,
+            Expr::Modulo(expr) => self.binary(expr, Operator::Percent)?,
+            Expr::Power(expr) => self.binary(expr, Operator::Caret)?,
+            Expr::Negation(expr) => self.unary(expr.inner(), Operator::Minus)?,
+            Expr::Root(expr) => self.call(Function::Root, &[expr.lhs(), expr.rhs()])?,
+            Expr::IntRoot(expr) => self.call(Function::IntRoot, &[expr.lhs(), expr.rhs()])?,
+            Expr::Square(expr) => self.call(Function::Square, &[expr.inner()])?,
+            Expr::Cube(expr) => self.call(Function::Cube, &[expr.inner()])?,
+            Expr::SquareRoot(expr) => self.call(Function::SquareRoot, &[expr.inner()])?,
+            Expr::CubeRoot(expr) => self.call(Function::CubeRoot, &[expr.inner()])?,
+            Expr::Reciprocal(expr) => self.call(Function::Reciprocal, &[expr.inner()])?,
+            expr => bail!(
+                "Expression {} can't be written in the expression language",
+                expr.token()
+            ),
+        };
+        Ok(written)
+    }
+
+    fn binary(
+        &self,
+        expr: &impl BinaryOperationExpr<ExprTree>,
+        operator: Operator,
+    ) -> Result<Written> {
+        let precedence = operator
+            .binary_precedence()
+            .expect("Operator of an expression is a binary operator");
+        let lhs = self.decompile(expr.lhs())?;
+        let rhs = self.decompile(expr.rhs())?;
+
+        let lhs = match operator.associativity() {
+            _ if lhs.precedence < precedence => lhs.parenthesised(),
+            Associativity::Right if lhs.precedence == precedence => lhs.parenthesised(),
+            _ => lhs.text,
+        };
+        // A unary operator takes everything binding tighter than itself as its operand,
+        // so it never needs parentheses as the right operand
+        let rhs = match operator.associativity() {
+            _ if rhs.unary => rhs.text,
+            _ if rhs.precedence < precedence => rhs.parenthesised(),
+            Associativity::Left if rhs.precedence == precedence => rhs.parenthesised(),
+            _ => rhs.text,
+        };
+
+        let text = match operator {
+            Operator::Caret | Operator::DoubleAsterisk => format!("{}{}{}", lhs, operator, rhs),
+            _ => format!("{} {} {}", lhs, operator, rhs),
+        };
+        Ok(Written::compound(text, precedence))
+    }
+
+    fn unary(&self, operand: &ExprTree, operator: Operator) -> Result<Written> {
+        let precedence = operator
+            .unary_precedence()
+            .expect("Operator of an expression is a unary operator");
+        // A minus right before a number literal would make a negative literal
+        let literal = is_number_literal(operand);
+        let operand = self.decompile(operand)?;
+        let operand = if operand.precedence < precedence || literal {
+            operand.parenthesised()
+        } else {
+            operand.text
+        };
+        Ok(Written::prefixed(
+            format!("{}{}", operator, operand),
+            precedence,
+        ))
+    }
+
+    fn call(&self, function: Function, arguments: &[&ExprTree]) -> Result<Written> {
+        let arguments = arguments
+            .iter()
+            .map(|argument| Ok(self.decompile(argument)?.text))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Written::atom(format!(
+            "{}({})",
+            function,
+            arguments.join(", ")
+        )))
+    }
+
+    /// A `var` declaration of all variables, if reading the formula wouldn't give them the same identifiers
+    ///
+    /// Variables can only be declared if their identifiers have no gaps, as the identifiers of a created
+    /// formula always start at zero.
+    fn declaration(&self, tree: &ExprTree) -> Result<Option<String>> {
+        let mut collector = AppearanceCollector {
+            identifiers: Vec::new(),
+        };
+        compose_tree(tree, &mut collector).context("Collecting variables of the expression.")?;
+        let used = collector.identifiers;
+
+        let mut identifiers: BTreeSet<&VariableLengthEnum> = used.iter().collect();
+        identifiers.extend(self.variable_names.keys());
+        let in_order: Vec<VariableLengthEnum> = (0..identifiers.len())
+            .map(VariableLengthEnum::from)
+            .collect();
+
+        if used == in_order || !in_order.iter().eq(identifiers) {
+            return Ok(None);
+        }
+        let names: Vec<String> = in_order
+            .iter()
+            .map(|identifier| self.variable_name(identifier))
+            .collect();
+        Ok(Some(format!("var {}", names.join(", "))))
+    }
+
+    fn variable_name(&self, identifier: &VariableLengthEnum) -> String {
+        if let Some(name) = self.variable_names.get(identifier) {
+            return written_name(name);
+        }
+
+        // Placeholder names start with underscores, as many as needed not to clash with named variables
+        let id: Result<usize, _> = identifier.clone().try_into();
+        let id = match id {
+            Ok(id) => id.to_string(),
+            Err(_) => format!("{:?}", identifier),
+        };
+        let mut placeholder = format!("_{}", id);
+        while self
+            .variable_names
+            .values()
+            .any(|name| *name == placeholder)
+        {
+            placeholder.insert(0, '_');
+        }
+        placeholder
+    }
+}
+
+/// Collects the identifiers of the variables of an expression in the order of their first use
+struct AppearanceCollector {
+    identifiers: Vec<VariableLengthEnum>,
+}
+
+impl Composer<()> for AppearanceCollector {
+    type Error = Infallible;
+
+    fn compose_default<E: ExprObj<()>>(
+        &mut self,
+        _expr: E,
+    ) -> Result<(), ComposeError<Self::Error>> {
+        Ok(())
+    }
+
+    fn compose_variable(
+        &mut self,
+        expr: ExprVariable<()>,
+    ) -> Result<(), ComposeError<Self::Error>> {
+        let identifier: VariableLengthEnum = expr.into();
+        if !self.identifiers.contains(&identifier) {
+            self.identifiers.push(identifier);
+        }
+        Ok(())
+    }
+}
+
+fn is_number_literal(tree: &ExprTree) -> bool {
+    matches!(
+        tree.inner(),
+        Expr::SignedIntLiteral(_)
+            | Expr::UnsignedIntLiteral(_)
+            | Expr::BinaryFloat32Literal(_)
+            | Expr::BinaryFloat64Literal(_)
+    )
+}
+
+/// Writes a float so that it is read back as a float with the same value
+///
+/// Both display forms are the shortest text that reads back as the same value, the exponent form
+/// is used when it is shorter, e.g. `6.022e23`. Whole numbers get a decimal point.
+fn float_text(value: f64) -> String {
+    let plain = value.to_string();
+    let plain = if plain.contains('.') {
+        plain
+    } else {
+        format!("{}.0", plain)
+    };
+    let exponent = format!("{:e}", value);
+    if exponent.len() < plain.len() {
+        exponent
+    } else {
+        plain
+    }
+}
+
+/// The name as it is written in the expression language, quoted if it isn't an identifier or is the name of a
+/// constant
+fn written_name(name: &str) -> String {
+    if Constant::from_name(name).is_some() {
+        quote_identifier(name)
+    } else {
+        written_identifier(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fef::v0::{
+        expr::{
+            Expr, ExprAddition, ExprBinaryFloat32Literal, ExprBinaryFloat64Literal,
+            ExprFalseLiteral, ExprNegation, ExprPower, ExprSignedIntLiteral, ExprTree,
+            ExprTrueLiteral, ExprUnsignedIntLiteral, ExprVariable,
+        },
+        raw::VariableLengthEnum,
+    };
+
+    use super::decompile;
+    use crate::{
+        constants::Constant,
+        into_expr_tree::{
+            into_expr_tree, ImplicitMultiplication, LiteralEncoding, ParseOptions, VariableOrder,
+        },
+        parser::Tokens,
+    };
+
+    fn two() -> Expr<ExprTree> {
+        ExprUnsignedIntLiteral::from(2u64).into()
+    }
+
+    fn negation(literal: Expr<ExprTree>) -> Expr<ExprTree> {
+        ExprNegation::from(ExprTree::from(literal)).into()
+    }
+
+    /// The literal added to the variable `x`
+    fn sum_with_literal(literal: Expr<ExprTree>) -> ExprTree {
+        let variable: Expr<ExprTree> = ExprVariable::from(VariableLengthEnum::from(0)).into();
+        let expr: Expr<ExprTree> =
+            ExprAddition::from((ExprTree::from(literal), ExprTree::from(variable))).into();
+        expr.into()
+    }
+
+    /// The tree of the decompiled formula, created with the default options
+    fn round_trip(tree: &ExprTree) -> ExprTree {
+        let text = decompile(tree, &[(&VariableLengthEnum::from(0), "x")]).expect("Decompiles");
+        let options = ParseOptions {
+            literals: LiteralEncoding::Exact,
+            fold_negative_literals: true,
+            implicit_multiplication: ImplicitMultiplication::Off,
+            builtin_constants: true,
+            variables: Vec::new(),
+            variable_order: VariableOrder::Appearance,
+            strict_variables: false,
+        };
+        into_expr_tree(
+            &mut Tokens::new(text.chars()),
+            &mut Vec::new(),
+            &options,
+            &mut Vec::new(),
+        )
+        .unwrap_or_else(|error| panic!("`{}` can't be read back: {:?}", text, error))
+    }
+
+    #[test]
+    fn literals_round_trip() {
+        let literals: Vec<Expr<ExprTree>> = vec![
+            ExprUnsignedIntLiteral::from(0u64).into(),
+            ExprUnsignedIntLiteral::from(42u64).into(),
+            ExprUnsignedIntLiteral::from(u64::from(u32::MAX)).into(),
+            ExprSignedIntLiteral::from(-5i64).into(),
+            ExprSignedIntLiteral::from(0i64).into(),
+            ExprSignedIntLiteral::from(5i64).into(),
+            ExprSignedIntLiteral::from(i64::from(u32::MAX)).into(),
+            ExprSignedIntLiteral::from(5_000_000_000i64).into(),
+            ExprSignedIntLiteral::from(i64::MAX).into(),
+            ExprSignedIntLiteral::from(-i64::MAX).into(),
+            ExprBinaryFloat32Literal::from(0.5f32).into(),
+            ExprBinaryFloat32Literal::from(0.1f32).into(),
+            ExprBinaryFloat32Literal::from(-0.1f32).into(),
+            ExprBinaryFloat32Literal::from(f32::MAX).into(),
+            ExprBinaryFloat32Literal::from(f32::MIN_POSITIVE).into(),
+            ExprBinaryFloat32Literal::from(1e-45f32).into(),
+            ExprBinaryFloat64Literal::from(0.1f64).into(),
+            ExprBinaryFloat64Literal::from(0.5f64).into(),
+            ExprBinaryFloat64Literal::from(-0.5f64).into(),
+            ExprBinaryFloat64Literal::from(3.0f64).into(),
+            ExprBinaryFloat64Literal::from(1e30f64 as f32 as f64).into(),
+            ExprBinaryFloat64Literal::from(-2.5e-300f64).into(),
+            ExprBinaryFloat64Literal::from(6.02214076e23f64).into(),
+            ExprBinaryFloat64Literal::from(f64::MAX).into(),
+            ExprBinaryFloat64Literal::from(Constant::Pi.value()).into(),
+            ExprBinaryFloat64Literal::from(-Constant::Phi.value()).into(),
+            ExprTrueLiteral::from(()).into(),
+            ExprFalseLiteral::from(()).into(),
+            negation(two()),
+            negation(ExprSignedIntLiteral::from(-2i64).into()),
+            negation(ExprBinaryFloat32Literal::from(2.5f32).into()),
+            negation(ExprBinaryFloat64Literal::from(0.1f64).into()),
+            negation(ExprBinaryFloat64Literal::from(Constant::Pi.value()).into()),
+            negation(ExprTrueLiteral::from(()).into()),
+            ExprPower::from((ExprTree::from(negation(two())), ExprTree::from(two()))).into(),
+        ];
+        for literal in literals {
+            let tree = sum_with_literal(literal);
+            assert_eq!(round_trip(&tree), tree);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt::{Display, Write};
+
+/// Range of character offsets in the formula source, `end` is exclusive
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct Span {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+}
+
+impl Span {
+    pub(crate) fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Label {
+    pub(crate) span: Span,
+    pub(crate) message: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Severity {
+    Error,
+    Warning,
+}
+
+/// Stable identifiers of the problems reported in formula sources
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Code {
+    UnexpectedCharacter,
+    InvalidNumber,
+    UnclosedComment,
+    UnclosedQuotedIdentifier,
+    InvalidQuotedIdentifier,
+    UnknownCommand,
+    MissingArgument,
+    UnexpectedClosingParenthesis,
+    UnclosedParenthesis,
+    ExpectedExpression,
+    ExpectedOperator,
+    IllegalUnaryOperator,
+    IllegalBinaryOperator,
+    UnknownFunction,
+    WrongArgumentCount,
+    InvalidBinding,
+    DuplicateBinding,
+    CyclicBinding,
+    InvalidDeclaration,
+    DuplicateDeclaration,
+    UndeclaredVariable,
+    LossyLiteral,
+    ImplicitMultiplication,
+    ShadowedConstant,
+    UnusedBinding,
+    SplitNumber,
+}
+
+impl Code {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Code::UnexpectedCharacter => "E0001",
+            Code::InvalidNumber => "E0002",
+            Code::UnclosedComment => "E0003",
+            Code::UnclosedQuotedIdentifier => "E0004",
+            Code::InvalidQuotedIdentifier => "E0005",
+            Code::UnknownCommand => "E0006",
+            Code::MissingArgument => "E0007",
+            Code::UnexpectedClosingParenthesis => "E0101",
+            Code::UnclosedParenthesis => "E0102",
+            Code::ExpectedExpression => "E0103",
+            Code::ExpectedOperator => "E0104",
+            Code::IllegalUnaryOperator => "E0105",
+            Code::IllegalBinaryOperator => "E0106",
+            Code::UnknownFunction => "E0107",
+            Code::WrongArgumentCount => "E0108",
+            Code::InvalidBinding => "E0109",
+            Code::DuplicateBinding => "E0110",
+            Code::CyclicBinding => "E0111",
+            Code::InvalidDeclaration => "E0112",
+            Code::DuplicateDeclaration => "E0113",
+            Code::UndeclaredVariable => "E0114",
+            Code::LossyLiteral => "W0001",
+            Code::ImplicitMultiplication => "W0002",
+            Code::ShadowedConstant => "W0003",
+            Code::UnusedBinding => "W0004",
+            Code::SplitNumber => "W0005",
+        }
+    }
+}
+
+/// A problem found in a formula source, pointing at the location of the problem
+///
+/// Error diagnostics are returned as errors and warnings are collected while parsing. Both are rendered
+/// against the source text by [`Diagnostic::render`].
+#[derive(Debug, Clone)]
+pub(crate) struct Diagnostic {
+    pub(crate) severity: Severity,
+    pub(crate) code: Code,
+    pub(crate) message: String,
+    pub(crate) primary: Label,
+    pub(crate) secondary: Vec<Label>,
+    pub(crate) help: Option<String>,
+}
+
+impl Diagnostic {
+    pub(crate) fn error(code: Code, message: impl Into<String>, span: Span) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            primary: Label {
+                span,
+                message: None,
+            },
+            secondary: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub(crate) fn warning(code: Code, message: impl Into<String>, span: Span) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(code, message, span)
+        }
+    }
+
+    /// Sets the message shown next to the primary span
+    pub(crate) fn with_label(mut self, message: impl Into<String>) -> Self {
+        self.primary.message = Some(message.into());
+        self
+    }
+
+    pub(crate) fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: Some(message.into()),
+        });
+        self
+    }
+
+    pub(crate) fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Renders the diagnostic with excerpts of the source it was found in
+    ///
+    /// `origin` names the source in the location line, e.g. the path of the formula file.
+    pub(crate) fn render(&self, source: &str, origin: &str) -> String {
+        let lines = SourceLines::new(source);
+        let mut output = String::new();
+
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let _ = writeln!(
+            output,
+            "{}[{}]: {}",
+            severity,
+            self.code.as_str(),
+            self.message
+        );
+
+        let (line, column) = lines.location(self.primary.span.start);
+        let mut labels: Vec<(&Label, char)> = vec![(&self.primary, '^')];
+        labels.extend(self.secondary.iter().map(|label| (label, '-')));
+        labels.sort_by_key(|(label, _)| label.span.start);
+
+        let last_line = labels
+            .iter()
+            .map(|(label, _)| lines.location(label.span.start).0)
+            .max()
+            .unwrap_or(line);
+        let gutter = " ".repeat((last_line + 1).to_string().len());
+
+        let _ = writeln!(
+            output,
+            "{}--> {}:{}:{}",
+            gutter,
+            origin,
+            line + 1,
+            column + 1
+        );
+        let _ = writeln!(output, "{} |", gutter);
+
+        let mut previous_line: Option<usize> = None;
+        for (label, marker) in labels {
+            let (line, column) = lines.location(label.span.start);
+            if previous_line != Some(line) {
+                if previous_line.is_some_and(|previous| previous + 1 < line) {
+                    let _ = writeln!(output, "{}...", gutter);
+                }
+                let _ = writeln!(
+                    output,
+                    "{:>width$} | {}",
+                    line + 1,
+                    lines.line(line).replace('\t', " "),
+                    width = gutter.len()
+                );
+                previous_line = Some(line);
+            }
+
+            // Multi-line spans are underlined up to the end of their first line
+            let line_length = lines.line(line).chars().count();
+            let underline_end = label
+                .span
+                .end
+                .min(label.span.start + line_length.saturating_sub(column));
+            let length = underline_end.saturating_sub(label.span.start).max(1);
+            let _ = write!(
+                output,
+                "{} | {}{}",
+                gutter,
+                " ".repeat(column),
+                marker.to_string().repeat(length)
+            );
+            match &label.message {
+                Some(message) => {
+                    let _ = writeln!(output, " {}", message);
+                }
+                None => {
+                    let _ = writeln!(output);
+                }
+            }
+        }
+
+        if let Some(help) = &self.help {
+            let _ = writeln!(output, "{} |", gutter);
+            let _ = writeln!(output, "{} = help: {}", gutter, help);
+        }
+
+        output
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {} at {}",
+            self.code.as_str(),
+            self.message,
+            self.primary.span
+        )
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Renders the error against the source it was found in, if it is a [`Diagnostic`]
+///
+/// Returns the error to propagate further, which replaces a rendered diagnostic with a short summary.
+pub(crate) fn report(error: anyhow::Error, source: &str, origin: &str) -> anyhow::Error {
+    match error.downcast_ref::<Diagnostic>() {
+        Some(diagnostic) => {
+            eprint!("{}", diagnostic.render(source, origin));
+            anyhow::anyhow!("Could not parse formula due to the previous error")
+        }
+        None => error,
+    }
+}
+
+/// Lookup of line and column numbers of character offsets
+struct SourceLines<'a> {
+    lines: Vec<&'a str>,
+    /// Character offset of the start of each line
+    starts: Vec<usize>,
+}
+
+impl<'a> SourceLines<'a> {
+    fn new(source: &'a str) -> Self {
+        let lines: Vec<&str> = source.split('\n').collect();
+        let mut starts = Vec::with_capacity(lines.len());
+        let mut offset = 0;
+        for line in lines.iter() {
+            starts.push(offset);
+            offset += line.chars().count() + 1;
+        }
+        Self { lines, starts }
+    }
+
+    /// Zero based line and column of a character offset
+    fn location(&self, offset: usize) -> (usize, usize) {
+        let line = self.starts.partition_point(|start| *start <= offset) - 1;
+        (line, offset - self.starts[line])
+    }
+
+    fn line(&self, line: usize) -> &str {
+        self.lines[line].trim_end_matches('\r')
+    }
+}
@@ -1,32 +1,49 @@
-use std::{collections::BTreeMap, convert::Infallible, io::Read};
+use std::{collections::BTreeMap, fmt::Display};
 
-use anyhow::Context;
+use anyhow::anyhow;
 use fef::v0::{
-    config::DEFAULT_CONFIG,
     expr::{
         self,
         error::ComposeError,
         traits::{BinaryOperationExpr, Composer, UnaryOperationExpr},
+        ExprTree,
     },
     raw::VariableLengthEnum,
-    read::read_expression,
 };
 
-pub(crate) fn evaluate_stream_as_fef_expr(
-    read: &mut impl Read,
+use crate::compose_tree::infallible;
+
+pub(crate) fn evaluate_tree_as_fef_expr(
+    tree: &ExprTree,
     variable_values: BTreeMap<VariableLengthEnum, f64>,
 ) -> anyhow::Result<f64> {
     let mut evaluator = FefStreamEvaluator { variable_values };
-    let out = read_expression(read, &DEFAULT_CONFIG, &mut evaluator).context("FEF Read")?;
-    Ok(out)
+    crate::compose_tree::compose_tree(tree, &mut evaluator).map_err(|error| match error {
+        ComposeError::CustomError(error) => anyhow!(error),
+        error => anyhow!(error).context("FEF Evaluate"),
+    })
+}
+
+/// A variable of the expression has no value
+#[derive(Debug)]
+pub(crate) struct UnboundVariableError {
+    identifier: VariableLengthEnum,
 }
 
+impl Display for UnboundVariableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No value given for variable #{}", self.identifier)
+    }
+}
+
+impl std::error::Error for UnboundVariableError {}
+
 struct FefStreamEvaluator {
     variable_values: BTreeMap<VariableLengthEnum, f64>,
 }
 
 impl Composer<f64> for FefStreamEvaluator {
-    type Error = Infallible;
+    type Error = UnboundVariableError;
     fn compose_addition(
         &mut self,
         expr: expr::ExprAddition<f64>,
@@ -39,10 +56,7 @@ impl Composer<f64> for FefStreamEvaluator {
         &mut self,
         expr: expr::ExprBinaryFloat32Literal<f64>,
     ) -> Result<f64, ComposeError<Self::Error>> {
-        let inner: f32 = match expr.try_into() {
-            Ok(inner) => inner,
-            Err(_) => unreachable!("Infallible"),
-        };
+        let inner: f32 = infallible(expr.try_into());
         Ok(inner as f64)
     }
 
@@ -50,10 +64,7 @@ impl Composer<f64> for FefStreamEvaluator {
         &mut self,
         expr: expr::ExprBinaryFloat64Literal<f64>,
     ) -> Result<f64, ComposeError<Self::Error>> {
-        let inner: f64 = match expr.try_into() {
-            Ok(inner) => inner,
-            Err(_) => unreachable!("Infallible"),
-        };
+        let inner: f64 = infallible(expr.try_into());
         Ok(inner)
     }
 
@@ -153,10 +164,7 @@ impl Composer<f64> for FefStreamEvaluator {
         &mut self,
         expr: expr::ExprSignedIntLiteral<f64>,
     ) -> Result<f64, ComposeError<Self::Error>> {
-        let inner: i64 = match expr.try_into() {
-            Ok(inner) => inner,
-            Err(_) => unreachable!("Infallible"),
-        };
+        let inner: i64 = infallible(expr.try_into());
         Ok(inner as f64)
     }
 
@@ -194,10 +202,7 @@ impl Composer<f64> for FefStreamEvaluator {
         &mut self,
         expr: expr::ExprUnsignedIntLiteral<f64>,
     ) -> Result<f64, ComposeError<Self::Error>> {
-        let inner: u64 = match expr.try_into() {
-            Ok(inner) => inner,
-            Err(_) => unreachable!("Infallible"),
-        };
+        let inner: u64 = infallible(expr.try_into());
         Ok(inner as f64)
     }
 
@@ -206,6 +211,11 @@ impl Composer<f64> for FefStreamEvaluator {
         expr: expr::ExprVariable<f64>,
     ) -> Result<f64, ComposeError<Self::Error>> {
         let identifier: VariableLengthEnum = expr.into();
-        Ok(*self.variable_values.get(&identifier).expect("Variable not specified in metadata. This evaluator requires all variables to be specified."))
+        match self.variable_values.get(&identifier) {
+            Some(value) => Ok(*value),
+            None => Err(ComposeError::CustomError(UnboundVariableError {
+                identifier,
+            })),
+        }
     }
 }
@@ -0,0 +1,379 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, LowerExp},
+};
+
+use anyhow::{bail, Result};
+use fef::v0::{
+    expr::{
+        traits::{BinaryOperationExpr, ExprObj, UnaryOperationExpr},
+        Expr, ExprTree,
+    },
+    raw::VariableLengthEnum,
+};
+
+use crate::{
+    compose_tree::infallible,
+    constants::Constant,
+    into_expr_tree::Operator,
+    parser::GREEK_LETTER_MAP,
+    written::{negation_precedence, Written},
+};
+
+/// Typesets an expression tree in LaTeX maths notation, with the fewest parentheses needed
+///
+/// Divisions are written as fractions, roots with `\sqrt`, powers, squares and cubes as superscripts,
+/// integer divisions and integer roots in floor brackets and modulo with `\bmod`. Variable names are written
+/// with Greek letters as commands and the part after the first `_` as a subscript, unnamed variables as
+/// `x` with their identifier as a subscript.
+pub(crate) fn to_latex(
+    tree: &ExprTree,
+    variable_names: &[(&VariableLengthEnum, &str)],
+) -> Result<String> {
+    let writer = LatexWriter {
+        variable_names: variable_names.iter().copied().collect(),
+    };
+    Ok(writer.write(tree)?.text)
+}
+
+struct LatexWriter<'a> {
+    variable_names: BTreeMap<&'a VariableLengthEnum, &'a str>,
+}
+
+impl LatexWriter<'_> {
+    fn write(&self, tree: &ExprTree) -> Result<Written> {
+        let typeset = match tree.inner() {
+            Expr::Variable(expr) => {
+                let identifier: VariableLengthEnum = expr.clone().into();
+                Written::atom(self.variable(&identifier))
+            }
+            Expr::SignedIntLiteral(expr) => {
+                let value: i64 = infallible(expr.clone().try_into());
+                Written::number(value.to_string())
+            }
+            Expr::UnsignedIntLiteral(expr) => {
+                let value: u64 = infallible(expr.clone().try_into());
+                Written::number(value.to_string())
+            }
+            Expr::BinaryFloat32Literal(expr) => {
+                let value: f32 = infallible(expr.clone().try_into());
+                float(value, value.is_finite())
+            }
+            Expr::BinaryFloat64Literal(expr) => {
+                let value: f64 = infallible(expr.clone().try_into());
+                match Constant::from_value(value.abs()) {
+                    Some(constant) if value < 0.0 => {
+                        Written::number(format!("-{}", constant_latex(constant)))
+                    }
+                    Some(constant) => Written::number(constant_latex(constant).to_string()),
+                    None => float(value, value.is_finite()),
+                }
+            }
+            Expr::TrueLiteral(_) => Written::atom("\\mathrm{true}".to_string()),
+            Expr::FalseLiteral(_) => Written::atom("\\mathrm{false}".to_string()),
+            Expr::Addition(expr) => self.binary(expr, Operator::Plus, "+")?,
+            Expr::Subtraction(expr) => self.binary(expr, Operator::Minus, "-")?,
+            Expr::Multiplication(expr) => self.binary(expr, Operator::Asterisk, "\\cdot")?,
+            Expr::Modulo(expr) => self.binary(expr, Operator::Percent, "\\bmod")?,
+            Expr::Division(expr) => self.fraction(expr.lhs(), expr.rhs())?,
+            Expr::IntDivision(expr) => floor(self.fraction(expr.lhs(), expr.rhs())?),
+            Expr::Power(expr) => {
+                let exponent = self.write(expr.rhs())?.text;
+                self.power(expr.lhs(), &exponent)?
+            }
+            Expr::Negation(expr) => self.negation(expr.inner())?,
+            Expr::Root(expr) => self.root(Some(expr.lhs()), expr.rhs())?,
+            Expr::IntRoot(expr) => floor(self.root(Some(expr.lhs()), expr.rhs())?),
+            Expr::Square(expr) => self.power(expr.inner(), "2")?,
+            Expr::Cube(expr) => self.power(expr.inner(), "3")?,
+            Expr::SquareRoot(expr) => self.root(None, expr.inner())?,
+            Expr::CubeRoot(expr) => {
+                Written::closed(format!("\\sqrt[3]{{{}}}", self.write(expr.inner())?.text))
+            }
+            Expr::Reciprocal(expr) => {
+                Written::closed(format!("\\frac{{1}}{{{}}}", self.write(expr.inner())?.text))
+            }
+            expr => bail!("Expression {} can't be exported as LaTeX", expr.token()),
+        };
+        Ok(typeset)
+    }
+
+    fn binary(
+        &self,
+        expr: &impl BinaryOperationExpr<ExprTree>,
+        operator: Operator,
+        symbol: &str,
+    ) -> Result<Written> {
+        let precedence = operator
+            .binary_precedence()
+            .expect("Operator of an expression is a binary operator");
+        let lhs = self.write(expr.lhs())?;
+        let rhs = self.write(expr.rhs())?;
+
+        let lhs = if lhs.precedence < precedence {
+            parenthesised(lhs)
+        } else {
+            lhs.text
+        };
+        // A minus sign right after an operator is hard to read, so negative operands are parenthesised
+        let rhs = if rhs.unary || rhs.precedence <= precedence {
+            parenthesised(rhs)
+        } else {
+            rhs.text
+        };
+        Ok(Written::compound(
+            format!("{} {} {}", lhs, symbol, rhs),
+            precedence,
+        ))
+    }
+
+    fn negation(&self, operand: &ExprTree) -> Result<Written> {
+        let precedence = negation_precedence();
+        let operand = self.write(operand)?;
+        let operand = if operand.unary || operand.precedence < precedence {
+            parenthesised(operand)
+        } else {
+            operand.text
+        };
+        Ok(Written::prefixed(format!("-{}", operand), precedence))
+    }
+
+    fn fraction(&self, numerator: &ExprTree, denominator: &ExprTree) -> Result<Written> {
+        Ok(Written::closed(format!(
+            "\\frac{{{}}}{{{}}}",
+            self.write(numerator)?.text,
+            self.write(denominator)?.text
+        )))
+    }
+
+    fn power(&self, base: &ExprTree, exponent: &str) -> Result<Written> {
+        let base = self.write(base)?;
+        let base = if base.superscriptable {
+            base.text
+        } else {
+            parenthesised(base)
+        };
+        Ok(Written::compound(
+            format!("{}^{{{}}}", base, exponent),
+            Operator::Caret
+                .binary_precedence()
+                .expect("Caret is a binary operator"),
+        ))
+    }
+
+    fn root(&self, degree: Option<&ExprTree>, radicand: &ExprTree) -> Result<Written> {
+        let radicand = self.write(radicand)?.text;
+        Ok(Written::closed(match degree {
+            Some(degree) => format!("\\sqrt[{}]{{{}}}", self.write(degree)?.text, radicand),
+            None => format!("\\sqrt{{{}}}", radicand),
+        }))
+    }
+
+    fn variable(&self, identifier: &VariableLengthEnum) -> String {
+        match self.variable_names.get(identifier) {
+            Some(name) => name_latex(name),
+            None => format!("x_{{{}}}", identifier),
+        }
+    }
+}
+
+fn parenthesised(typeset: Written) -> String {
+    typeset.enclosed("\\left(", "\\right)")
+}
+
+/// The typeset expression in floor brackets, for integer divisions and integer roots
+fn floor(typeset: Written) -> Written {
+    Written::atom(format!("\\left\\lfloor {} \\right\\rfloor", typeset.text))
+}
+
+/// Float literals, in scientific notation if that is shorter, e.g. `6.022 \times 10^{23}`
+fn float(value: impl Display + LowerExp + PartialOrd + Default, finite: bool) -> Written {
+    let negative = value < Default::default();
+    if !finite {
+        let text = value.to_string();
+        return match text.trim_start_matches('-') {
+            "inf" => Written::number(format!("{}\\infty", if negative { "-" } else { "" })),
+            _ => Written::atom("\\mathrm{NaN}".to_string()),
+        };
+    }
+
+    let plain = value.to_string();
+    let exponent = format!("{:e}", value);
+    if exponent.len() >= plain.len() {
+        return Written::number(plain);
+    }
+    let (mantissa, exponent) = exponent.split_once('e').expect("Exponent form");
+    let text = format!("{} \\times 10^{{{}}}", mantissa, exponent);
+    let precedence = Operator::Asterisk
+        .binary_precedence()
+        .expect("Asterisk is a binary operator");
+    match negative {
+        true => Written::prefixed(text, precedence),
+        false => Written::compound(text, precedence),
+    }
+}
+
+/// A built-in constant in LaTeX, with `e`, `\tau` and `\varphi` upright to set them apart from variables
+fn constant_latex(constant: Constant) -> &'static str {
+    match constant {
+        Constant::Pi => "\\pi",
+        Constant::E => "\\mathrm{e}",
+        Constant::Tau => "\\mathrm{\\tau}",
+        Constant::Phi => "\\mathrm{\\varphi}",
+    }
+}
+
+/// The name of a variable in LaTeX, as read back by `--syntax latex`
+///
+/// Single letters and spelled out Greek letters are written as letters, e.g. `\theta` for `theta`, and longer
+/// names upright, e.g. `\mathrm{rate}`. The part after the first `_` is a subscript, e.g. `x_{1}` or
+/// `\theta_{\omega}`. Names with other characters are written as text, and the names of constants in italics,
+/// e.g. `\mathit{e}`, which is always read as a variable.
+fn name_latex(name: &str) -> String {
+    if Constant::from_name(name).is_some() && greek_word(name).is_none() {
+        return format!("\\mathit{{{}}}", name);
+    }
+    if let Some((base, subscript)) = name.split_once('_') {
+        if is_plain(base) && is_plain(subscript) {
+            let subscript = greek_word(subscript).unwrap_or_else(|| letters_latex(subscript));
+            return format!("{}_{{{}}}", symbol_latex(base), subscript);
+        }
+    }
+    if is_plain(name) {
+        symbol_latex(name)
+    } else {
+        format!("\\text{{{}}}", escape_text(name))
+    }
+}
+
+/// Whether the text is not empty and has only ASCII letters, digits and Greek letters
+fn is_plain(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || greek_command(c).is_some())
+}
+
+fn symbol_latex(text: &str) -> String {
+    if let Some(letter) = greek_word(text) {
+        return letter;
+    }
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() || greek_command(c).is_some() => {
+            letters_latex(text)
+        }
+        _ => format!("\\mathrm{{{}}}", letters_latex(text)),
+    }
+}
+
+/// The text with Greek letters as commands
+fn letters_latex(text: &str) -> String {
+    let mut latex = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match greek_command(c) {
+            Some(command) => {
+                latex.push('\\');
+                latex.push_str(command);
+                // A letter right after the command would continue its name
+                if chars.peek().is_some_and(|next| next.is_ascii_alphabetic()) {
+                    latex.push(' ');
+                }
+            }
+            None => latex.push(c),
+        }
+    }
+    latex
+}
+
+/// The command of the Greek letter spelled out by the whole text, e.g. `\theta` for `theta`
+fn greek_word(text: &str) -> Option<String> {
+    GREEK_LETTER_MAP
+        .contains_key(text)
+        .then(|| format!("\\{}", text))
+}
+
+fn greek_command(letter: char) -> Option<&'static str> {
+    GREEK_LETTER_MAP
+        .entries()
+        .find(|(_, greek)| **greek == letter)
+        .map(|(command, _)| *command)
+}
+
+/// Escapes the characters that are special in LaTeX text
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '{' | '}' | '#' | '$' | '%' | '&' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_latex;
+    use crate::read_fef::FefFile;
+
+    fn latex(formula: &str) -> String {
+        let fef_file = FefFile::parse("Test", formula);
+        to_latex(&fef_file.expression, &fef_file.variable_names()).expect("Typesets")
+    }
+
+    #[test]
+    fn fractions_and_roots() {
+        assert_eq!(
+            latex("-(a + b)^2 / (c - -d) + sqrt(x) * cbrt(y) - root(3, z) % 2"),
+            r"\frac{-\left(a + b\right)^{2}}{c - \left(-d\right)} + \sqrt{x} \cdot \sqrt[3]{y} - \sqrt[3]{z} \bmod 2"
+        );
+        assert_eq!(
+            latex("a - b - (c - d) + a * (b / c) / (d * e)"),
+            r"a - b - \left(c - d\right) + \frac{a \cdot \frac{b}{c}}{d \cdot \mathrm{e}}"
+        );
+    }
+
+    #[test]
+    fn powers_and_negations() {
+        assert_eq!(
+            latex("2^3^2 + (2^3)^2 - -x^2 + (-x)^2 + x^-2"),
+            r"2^{3^{2}} + \left(2^{3}\right)^{2} - \left(-x^{2}\right) + \left(-x\right)^{2} + x^{-2}"
+        );
+        assert_eq!(
+            latex("sq(a + b) + cube(-a) + sq(-a) + (-2)^x + sq(a / b)"),
+            r"\left(a + b\right)^{2} + \left(-a\right)^{3} + \left(-a\right)^{2} + \left(-2\right)^{x} + \left(\frac{a}{b}\right)^{2}"
+        );
+    }
+
+    #[test]
+    fn integer_operations() {
+        assert_eq!(
+            latex("a // b + introot(3, a) + recip(x) + a % b % c"),
+            r"\left\lfloor \frac{a}{b} \right\rfloor + \left\lfloor \sqrt[3]{a} \right\rfloor + \frac{1}{x} + a \bmod b \bmod c"
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            latex("-x + 0.1 + 1e300 * 3.5 - 1e-30"),
+            r"-x + 0.1 + 1 \times 10^{300} \cdot 3.5 - 1 \times 10^{-30}"
+        );
+    }
+
+    #[test]
+    fn variable_names() {
+        assert_eq!(
+            latex("x_1 * theta + alpha_max + rate + \"e\" + \"my var\""),
+            r"x_{1} \cdot \theta + \alpha_{max} + \mathrm{rate} + \mathit{e} + \text{my var}"
+        );
+    }
+}
@@ -0,0 +1,482 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use fef::v0::{
+    expr::{
+        traits::{BinaryOperationExpr, ExprObj, UnaryOperationExpr},
+        Expr, ExprTree,
+    },
+    raw::VariableLengthEnum,
+};
+
+use crate::{
+    compose_tree::infallible,
+    constants::Constant,
+    into_expr_tree::Operator,
+    written::{negation_precedence, Written},
+};
+
+/// Writes an expression tree as an XHTML document with the formula in MathML, titled with the name of the formula
+///
+/// Presentation MathML shows the formula the way it is typeset, with the fewest parentheses needed. Content
+/// MathML describes what it computes, e.g. integer division as the floor of a division.
+pub(crate) fn to_mathml(
+    tree: &ExprTree,
+    name: Option<&str>,
+    variable_names: &[(&VariableLengthEnum, &str)],
+    content: bool,
+) -> Result<String> {
+    let writer = MathmlWriter {
+        variable_names: variable_names.iter().copied().collect(),
+    };
+    let math = if content {
+        writer.content(tree)?
+    } else {
+        writer.presentation(tree)?.text
+    };
+    Ok(format!(
+        concat!(
+            "<!DOCTYPE html>\n",
+            "<html xmlns=\"http://www.w3.org/1999/xhtml\">\n",
+            "<head>\n",
+            "<meta charset=\"utf-8\"/>\n",
+            "<title>{}</title>\n",
+            "</head>\n",
+            "<body>\n",
+            "<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\">{}</math>\n",
+            "</body>\n",
+            "</html>",
+        ),
+        escape(name.unwrap_or("Formula")),
+        math
+    ))
+}
+
+struct MathmlWriter<'a> {
+    variable_names: BTreeMap<&'a VariableLengthEnum, &'a str>,
+}
+
+impl MathmlWriter<'_> {
+    fn presentation(&self, tree: &ExprTree) -> Result<Written> {
+        let presentation = match tree.inner() {
+            Expr::Variable(expr) => {
+                let identifier: VariableLengthEnum = expr.clone().into();
+                Written::atom(match self.variable_names.get(&identifier) {
+                    Some(name) => name_presentation(name),
+                    None => format!("<msub><mi>x</mi><mn>{}</mn></msub>", identifier),
+                })
+            }
+            Expr::SignedIntLiteral(expr) => {
+                let value: i64 = infallible(expr.clone().try_into());
+                number(value < 0, format!("<mn>{}</mn>", value.unsigned_abs()))
+            }
+            Expr::UnsignedIntLiteral(expr) => {
+                let value: u64 = infallible(expr.clone().try_into());
+                number(false, format!("<mn>{}</mn>", value))
+            }
+            Expr::BinaryFloat32Literal(expr) => {
+                let value: f32 = infallible(expr.clone().try_into());
+                float_presentation(value.to_string(), format!("{:e}", value))
+            }
+            Expr::BinaryFloat64Literal(expr) => {
+                let value: f64 = infallible(expr.clone().try_into());
+                match Constant::from_value(value.abs()) {
+                    Some(constant) => number(
+                        value < 0.0,
+                        format!("<mi>{}</mi>", constant_presentation(constant)),
+                    ),
+                    None => float_presentation(value.to_string(), format!("{:e}", value)),
+                }
+            }
+            Expr::TrueLiteral(_) => Written::atom("<mi>true</mi>".to_string()),
+            Expr::FalseLiteral(_) => Written::atom("<mi>false</mi>".to_string()),
+            Expr::Addition(expr) => self.binary(expr, Operator::Plus, "+")?,
+            Expr::Subtraction(expr) => self.binary(expr, Operator::Minus, "\u{2212}")?,
+            Expr::Multiplication(expr) => self.binary(expr, Operator::Asterisk, "\u{22c5}")?,
+            Expr::Modulo(expr) => self.binary(expr, Operator::Percent, "mod")?,
+            Expr::Division(expr) => self.fraction(expr.lhs(), expr.rhs())?,
+            Expr::IntDivision(expr) => floor(self.fraction(expr.lhs(), expr.rhs())?),
+            Expr::Power(expr) => {
+                let exponent = self.presentation(expr.rhs())?.text;
+                self.power(expr.lhs(), exponent)?
+            }
+            Expr::Negation(expr) => self.negation(expr.inner())?,
+            Expr::Root(expr) => {
+                let degree = self.presentation(expr.lhs())?.text;
+                self.root(Some(degree), expr.rhs())?
+            }
+            Expr::IntRoot(expr) => {
+                let degree = self.presentation(expr.lhs())?.text;
+                floor(self.root(Some(degree), expr.rhs())?)
+            }
+            Expr::Square(expr) => self.power(expr.inner(), "<mn>2</mn>".to_string())?,
+            Expr::Cube(expr) => self.power(expr.inner(), "<mn>3</mn>".to_string())?,
+            Expr::SquareRoot(expr) => self.root(None, expr.inner())?,
+            Expr::CubeRoot(expr) => self.root(Some("<mn>3</mn>".to_string()), expr.inner())?,
+            Expr::Reciprocal(expr) => Written::closed(format!(
+                "<mfrac><mn>1</mn><mrow>{}</mrow></mfrac>",
+                self.presentation(expr.inner())?.text
+            )),
+            expr => bail!("Expression {} can't be exported as MathML", expr.token()),
+        };
+        Ok(presentation)
+    }
+
+    fn binary(
+        &self,
+        expr: &impl BinaryOperationExpr<ExprTree>,
+        operator: Operator,
+        symbol: &str,
+    ) -> Result<Written> {
+        let precedence = operator
+            .binary_precedence()
+            .expect("Operator of an expression is a binary operator");
+        let lhs = self.presentation(expr.lhs())?;
+        let rhs = self.presentation(expr.rhs())?;
+
+        let lhs = if lhs.precedence < precedence {
+            parenthesised(lhs)
+        } else {
+            lhs.text
+        };
+        // Negative right operands are parenthesised, as in the LaTeX export
+        let rhs = if rhs.unary || rhs.precedence <= precedence {
+            parenthesised(rhs)
+        } else {
+            rhs.text
+        };
+        Ok(Written::compound(
+            format!("<mrow>{}<mo>{}</mo>{}</mrow>", lhs, symbol, rhs),
+            precedence,
+        ))
+    }
+
+    fn negation(&self, operand: &ExprTree) -> Result<Written> {
+        let operand = self.presentation(operand)?;
+        let operand = if operand.unary || operand.precedence < negation_precedence() {
+            parenthesised(operand)
+        } else {
+            operand.text
+        };
+        Ok(number(true, operand))
+    }
+
+    fn fraction(&self, numerator: &ExprTree, denominator: &ExprTree) -> Result<Written> {
+        Ok(Written::closed(format!(
+            "<mfrac><mrow>{}</mrow><mrow>{}</mrow></mfrac>",
+            self.presentation(numerator)?.text,
+            self.presentation(denominator)?.text
+        )))
+    }
+
+    fn power(&self, base: &ExprTree, exponent: String) -> Result<Written> {
+        let base = self.presentation(base)?;
+        let base = if base.superscriptable {
+            base.text
+        } else {
+            parenthesised(base)
+        };
+        Ok(Written::compound(
+            format!("<msup>{}<mrow>{}</mrow></msup>", base, exponent),
+            Operator::Caret
+                .binary_precedence()
+                .expect("Caret is a binary operator"),
+        ))
+    }
+
+    fn root(&self, degree: Option<String>, radicand: &ExprTree) -> Result<Written> {
+        let radicand = self.presentation(radicand)?.text;
+        Ok(Written::closed(match degree {
+            Some(degree) => format!(
+                "<mroot><mrow>{}</mrow><mrow>{}</mrow></mroot>",
+                radicand, degree
+            ),
+            None => format!("<msqrt>{}</msqrt>", radicand),
+        }))
+    }
+
+    fn content(&self, tree: &ExprTree) -> Result<String> {
+        let content = match tree.inner() {
+            Expr::Variable(expr) => {
+                let identifier: VariableLengthEnum = expr.clone().into();
+                match self.variable_names.get(&identifier) {
+                    Some(name) => format!("<ci>{}</ci>", escape(name)),
+                    None => format!("<ci>x_{}</ci>", identifier),
+                }
+            }
+            Expr::SignedIntLiteral(expr) => {
+                let value: i64 = infallible(expr.clone().try_into());
+                format!("<cn type=\"integer\">{}</cn>", value)
+            }
+            Expr::UnsignedIntLiteral(expr) => {
+                let value: u64 = infallible(expr.clone().try_into());
+                format!("<cn type=\"integer\">{}</cn>", value)
+            }
+            Expr::BinaryFloat32Literal(expr) => {
+                let value: f32 = infallible(expr.clone().try_into());
+                // The exact value of the 32-bit float, not its shortest decimal form
+                float_content(f64::from(value))
+            }
+            Expr::BinaryFloat64Literal(expr) => {
+                let value: f64 = infallible(expr.clone().try_into());
+                match Constant::from_value(value.abs()) {
+                    Some(Constant::Pi) => sign_content(value, "<pi/>"),
+                    Some(Constant::E) => sign_content(value, "<exponentiale/>"),
+                    _ => float_content(value),
+                }
+            }
+            Expr::TrueLiteral(_) => "<true/>".to_string(),
+            Expr::FalseLiteral(_) => "<false/>".to_string(),
+            Expr::Addition(expr) => self.apply("plus", &[expr.lhs(), expr.rhs()])?,
+            Expr::Subtraction(expr) => self.apply("minus", &[expr.lhs(), expr.rhs()])?,
+            Expr::Multiplication(expr) => self.apply("times", &[expr.lhs(), expr.rhs()])?,
+            Expr::Division(expr) => self.apply("divide", &[expr.lhs(), expr.rhs()])?,
+            Expr::IntDivision(expr) => format!(
+                "<apply><floor/>{}</apply>",
+                self.apply("divide", &[expr.lhs(), expr.rhs()])?
+            ),
+            Expr::Modulo(expr) => self.apply("rem", &[expr.lhs(), expr.rhs()])?,
+            Expr::Power(expr) => self.apply("power", &[expr.lhs(), expr.rhs()])?,
+            Expr::Negation(expr) => self.apply("minus", &[expr.inner()])?,
+            Expr::Root(expr) => format!(
+                "<apply><root/><degree>{}</degree>{}</apply>",
+                self.content(expr.lhs())?,
+                self.content(expr.rhs())?
+            ),
+            Expr::IntRoot(expr) => format!(
+                "<apply><floor/><apply><root/><degree>{}</degree>{}</apply></apply>",
+                self.content(expr.lhs())?,
+                self.content(expr.rhs())?
+            ),
+            Expr::Square(expr) => format!(
+                "<apply><power/>{}<cn type=\"integer\">2</cn></apply>",
+                self.content(expr.inner())?
+            ),
+            Expr::Cube(expr) => format!(
+                "<apply><power/>{}<cn type=\"integer\">3</cn></apply>",
+                self.content(expr.inner())?
+            ),
+            Expr::SquareRoot(expr) => self.apply("root", &[expr.inner()])?,
+            Expr::CubeRoot(expr) => format!(
+                "<apply><root/><degree><cn type=\"integer\">3</cn></degree>{}</apply>",
+                self.content(expr.inner())?
+            ),
+            Expr::Reciprocal(expr) => format!(
+                "<apply><divide/><cn type=\"integer\">1</cn>{}</apply>",
+                self.content(expr.inner())?
+            ),
+            expr => bail!("Expression {} can't be exported as MathML", expr.token()),
+        };
+        Ok(content)
+    }
+
+    fn apply(&self, function: &str, arguments: &[&ExprTree]) -> Result<String> {
+        let arguments = arguments
+            .iter()
+            .map(|argument| self.content(argument))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(format!(
+            "<apply><{}/>{}</apply>",
+            function,
+            arguments.concat()
+        ))
+    }
+}
+
+fn parenthesised(presentation: Written) -> String {
+    presentation.enclosed("<mrow><mo>(</mo>", "<mo>)</mo></mrow>")
+}
+
+/// A literal or a negation, which is preceded by a minus sign if it is negative
+fn number(negative: bool, markup: String) -> Written {
+    if negative {
+        Written::prefixed(
+            format!("<mrow><mo>\u{2212}</mo>{}</mrow>", markup),
+            negation_precedence(),
+        )
+    } else {
+        Written::atom(markup)
+    }
+}
+
+/// A float literal given in its plain and exponent forms, in scientific notation if that is shorter
+fn float_presentation(plain: String, exponent: String) -> Written {
+    let negative = plain.starts_with('-');
+    let magnitude = |text: &str| text.trim_start_matches('-').to_string();
+    match magnitude(&plain).as_str() {
+        "inf" => return number(negative, "<mi>\u{221e}</mi>".to_string()),
+        "NaN" => return Written::atom("<mi>NaN</mi>".to_string()),
+        _ => {}
+    }
+    if exponent.len() >= plain.len() {
+        return number(negative, format!("<mn>{}</mn>", magnitude(&plain)));
+    }
+
+    let (mantissa, exponent) = exponent.split_once('e').expect("Exponent form");
+    let exponent = match exponent.strip_prefix('-') {
+        Some(exponent) => format!("<mrow><mo>\u{2212}</mo><mn>{}</mn></mrow>", exponent),
+        None => format!("<mn>{}</mn>", exponent),
+    };
+    let markup = format!(
+        "<mrow><mn>{}</mn><mo>\u{00d7}</mo><msup><mn>10</mn>{}</msup></mrow>",
+        magnitude(mantissa),
+        exponent
+    );
+    if negative {
+        number(true, markup)
+    } else {
+        Written::compound(
+            markup,
+            Operator::Asterisk
+                .binary_precedence()
+                .expect("Asterisk is a binary operator"),
+        )
+    }
+}
+
+/// The typeset expression in floor brackets, for integer divisions and integer roots
+fn floor(presentation: Written) -> Written {
+    Written::atom(format!(
+        "<mrow><mo>\u{230a}</mo>{}<mo>\u{230b}</mo></mrow>",
+        presentation.text
+    ))
+}
+
+fn constant_presentation(constant: Constant) -> &'static str {
+    match constant {
+        Constant::Pi => "\u{3c0}",
+        Constant::E => "e",
+        Constant::Tau => "\u{3c4}",
+        Constant::Phi => "\u{3c6}",
+    }
+}
+
+/// The name of a variable, with the part after the first `_` as a subscript
+fn name_presentation(name: &str) -> String {
+    match name.split_once('_') {
+        Some((base, subscript)) if !base.is_empty() && !subscript.is_empty() => {
+            let subscript = if subscript.chars().all(|c| c.is_ascii_digit()) {
+                format!("<mn>{}</mn>", subscript)
+            } else {
+                format!("<mi>{}</mi>", escape(subscript))
+            };
+            format!("<msub><mi>{}</mi>{}</msub>", escape(base), subscript)
+        }
+        _ => format!("<mi>{}</mi>", escape(name)),
+    }
+}
+
+fn float_content(value: f64) -> String {
+    if value.is_nan() {
+        "<notanumber/>".to_string()
+    } else if value.is_infinite() {
+        sign_content(value, "<infinity/>")
+    } else {
+        format!("<cn type=\"double\">{}</cn>", value)
+    }
+}
+
+/// The content of a constant, negated if the value is negative
+fn sign_content(value: f64, content: &str) -> String {
+    if value < 0.0 {
+        format!("<apply><minus/>{}</apply>", content)
+    } else {
+        content.to_string()
+    }
+}
+
+/// Escapes the characters that are special in XML
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_mathml;
+    use crate::read_fef::FefFile;
+
+    /// The `math` element of the formula
+    fn math(formula: &str, content: bool) -> String {
+        let fef_file = FefFile::parse("Test", formula);
+        let document = to_mathml(
+            &fef_file.expression,
+            None,
+            &fef_file.variable_names(),
+            content,
+        )
+        .expect("Writes");
+        let start = document.find("<math").expect("Has a math element");
+        let end = document.find("</math>").expect("Has a math element") + "</math>".len();
+        document[start..end].replace(
+            " xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\"",
+            "",
+        )
+    }
+
+    #[test]
+    fn document_is_titled_with_the_escaped_name() {
+        let fef_file = FefFile::parse("Test", "x");
+        let document = to_mathml(
+            &fef_file.expression,
+            Some("a < b & c"),
+            &fef_file.variable_names(),
+            false,
+        )
+        .unwrap();
+        assert!(document.starts_with("<!DOCTYPE html>\n"));
+        assert!(document.contains("<title>a &lt; b &amp; c</title>"));
+        assert!(document.contains("<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\"><mi>x</mi></math>"));
+        assert!(document.ends_with("</html>"));
+    }
+
+    #[test]
+    fn presentation() {
+        assert_eq!(
+            math("-(a + b)^2 / (c - -d)", false),
+            "<math><mfrac><mrow><mrow><mo>−</mo><msup><mrow><mo>(</mo><mrow><mi>a</mi><mo>+</mo><mi>b</mi></mrow><mo>)</mo></mrow><mrow><mn>2</mn></mrow></msup></mrow></mrow><mrow><mrow><mi>c</mi><mo>−</mo><mrow><mo>(</mo><mrow><mo>−</mo><mi>d</mi></mrow><mo>)</mo></mrow></mrow></mrow></mfrac></math>"
+        );
+        assert_eq!(
+            math("sqrt(x) * cbrt(y) - root(3, z) % 2", false),
+            "<math><mrow><mrow><msqrt><mi>x</mi></msqrt><mo>⋅</mo><mroot><mrow><mi>y</mi></mrow><mrow><mn>3</mn></mrow></mroot></mrow><mo>−</mo><mrow><mroot><mrow><mi>z</mi></mrow><mrow><mn>3</mn></mrow></mroot><mo>mod</mo><mn>2</mn></mrow></mrow></math>"
+        );
+        assert_eq!(
+            math("2^3^2 + x^-2", false),
+            "<math><mrow><msup><mn>2</mn><mrow><msup><mn>3</mn><mrow><mn>2</mn></mrow></msup></mrow></msup><mo>+</mo><msup><mi>x</mi><mrow><mrow><mo>−</mo><mn>2</mn></mrow></mrow></msup></mrow></math>"
+        );
+        assert_eq!(
+            math("pi * e + 6.022e23", false),
+            "<math><mrow><mrow><mi>π</mi><mo>⋅</mo><mi>e</mi></mrow><mo>+</mo><mrow><mn>6.022</mn><mo>×</mo><msup><mn>10</mn><mn>23</mn></msup></mrow></mrow></math>"
+        );
+        assert_eq!(
+            math("x_1 * alpha_max + \"my var\"", false),
+            "<math><mrow><mrow><msub><mi>x</mi><mn>1</mn></msub><mo>⋅</mo><msub><mi>alpha</mi><mi>max</mi></msub></mrow><mo>+</mo><mi>my var</mi></mrow></math>"
+        );
+    }
+
+    #[test]
+    fn content() {
+        assert_eq!(
+            math("-(a + b)^2 / (c - -d)", true),
+            "<math><apply><divide/><apply><minus/><apply><power/><apply><plus/><ci>a</ci><ci>b</ci></apply><cn type=\"integer\">2</cn></apply></apply><apply><minus/><ci>c</ci><apply><minus/><ci>d</ci></apply></apply></apply></math>"
+        );
+        assert_eq!(
+            math("cbrt(y) % 2 + x^-2", true),
+            "<math><apply><plus/><apply><rem/><apply><root/><degree><cn type=\"integer\">3</cn></degree><ci>y</ci></apply><cn type=\"integer\">2</cn></apply><apply><power/><ci>x</ci><cn type=\"integer\">-2</cn></apply></apply></math>"
+        );
+        assert_eq!(
+            math("pi * e + 3.0 * true - false", true),
+            "<math><apply><minus/><apply><plus/><apply><times/><pi/><exponentiale/></apply><apply><times/><cn type=\"double\">3</cn><true/></apply></apply><false/></apply></math>"
+        );
+    }
+}
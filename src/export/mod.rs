@@ -0,0 +1,30 @@
+mod latex;
+mod mathml;
+
+use anyhow::Result;
+
+use crate::read_fef::FefFile;
+
+/// Notation a formula is exported in
+#[derive(Debug, PartialEq, Eq, Clone, Copy, clap::ValueEnum)]
+pub(crate) enum ExportFormat {
+    /// LaTeX maths notation, as typeset in documents
+    Latex,
+    /// An XHTML document with the formula in Presentation MathML, for display
+    Mathml,
+    /// An XHTML document with the formula in Content MathML, for semantic interchange
+    ContentMathml,
+}
+
+/// The formula of a FEF file written in the format
+pub(crate) fn export(fef_file: &FefFile, format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Latex => latex::to_latex(&fef_file.expression, &fef_file.variable_names()),
+        ExportFormat::Mathml | ExportFormat::ContentMathml => mathml::to_mathml(
+            &fef_file.expression,
+            fef_file.name()?,
+            &fef_file.variable_names(),
+            format == ExportFormat::ContentMathml,
+        ),
+    }
+}
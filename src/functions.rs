@@ -0,0 +1,100 @@
+use std::fmt::Display;
+
+use fef::v0::expr::{
+    Expr, ExprCube, ExprCubeRoot, ExprIntRoot, ExprReciprocal, ExprRoot, ExprSquare,
+    ExprSquareRoot, ExprTree,
+};
+use phf::{phf_map, Map};
+
+/// Functions callable from the expression language, each mapped onto a specialised FEF expression
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Function {
+    SquareRoot,
+    CubeRoot,
+    Root,
+    IntRoot,
+    Reciprocal,
+    Square,
+    Cube,
+}
+
+const FUNCTION_MAP: Map<&'static str, Function> = phf_map!(
+    "sqrt" => Function::SquareRoot,
+    "cbrt" => Function::CubeRoot,
+    "root" => Function::Root,
+    "introot" => Function::IntRoot,
+    "recip" => Function::Reciprocal,
+    "sq" => Function::Square,
+    "cube" => Function::Cube,
+);
+
+impl Function {
+    pub(crate) fn from_name(name: &str) -> Option<Function> {
+        FUNCTION_MAP.get(name).copied()
+    }
+
+    /// Names of all functions, in alphabetical order
+    pub(crate) fn names() -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = FUNCTION_MAP.keys().copied().collect();
+        names.sort();
+        names
+    }
+
+    /// The name under which the function is called in the expression language
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Function::SquareRoot => "sqrt",
+            Function::CubeRoot => "cbrt",
+            Function::Root => "root",
+            Function::IntRoot => "introot",
+            Function::Reciprocal => "recip",
+            Function::Square => "sq",
+            Function::Cube => "cube",
+        }
+    }
+
+    /// Number of arguments the function takes
+    pub(crate) fn arity(&self) -> usize {
+        match self {
+            Function::Root | Function::IntRoot => 2,
+            _ => 1,
+        }
+    }
+
+    /// Composes the function with its arguments into an expression tree
+    ///
+    /// # Panics
+    /// Panics if the number of arguments doesn't match the arity of the function
+    pub(crate) fn compose(&self, arguments: Vec<ExprTree>) -> ExprTree {
+        assert_eq!(
+            arguments.len(),
+            self.arity(),
+            "Function composed with wrong number of arguments"
+        );
+        let mut arguments = arguments.into_iter();
+        let mut next = || arguments.next().expect("Arity checked");
+
+        let expr: Expr<ExprTree> = match self {
+            Function::SquareRoot => ExprSquareRoot::from(next()).into(),
+            Function::CubeRoot => ExprCubeRoot::from(next()).into(),
+            Function::Reciprocal => ExprReciprocal::from(next()).into(),
+            Function::Square => ExprSquare::from(next()).into(),
+            Function::Cube => ExprCube::from(next()).into(),
+            Function::Root => {
+                let degree = next();
+                ExprRoot::from((degree, next())).into()
+            }
+            Function::IntRoot => {
+                let degree = next();
+                ExprIntRoot::from((degree, next())).into()
+            }
+        };
+        expr.into()
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,555 @@
+use std::{collections::BTreeMap, fmt::Write as _, io::Read};
+
+use anyhow::{bail, Context, Result};
+use fef::v0::{
+    config::{OverridableConfig, DEFAULT_CONFIG},
+    metadata::{MetadataHeader, MetadataRecord},
+    raw::VariableLengthEnum,
+    tokens::{ExprToken, FileContentTypeToken},
+    traits::ReadFrom,
+};
+use serde::Serialize;
+
+/// Structure of a FEF file with the offset and raw bytes of each of its parts
+///
+/// Expression nodes are read from the raw bytes directly, because an expression tree doesn't
+/// keep the encoding its literals were read from.
+#[derive(Serialize)]
+pub(crate) struct Inspection {
+    version: Item<usize>,
+    content_type: Item<String>,
+    configuration: Option<Configuration>,
+    metadata: Option<Metadata>,
+    expression: Node,
+    /// Bytes after the end of the expression
+    trailing: Option<Item<usize>>,
+}
+
+#[derive(Serialize)]
+struct Item<T> {
+    offset: usize,
+    bytes: Bytes,
+    value: T,
+}
+
+#[derive(Serialize)]
+struct Configuration {
+    offset: usize,
+    bytes: Bytes,
+    entries: Vec<ConfigurationEntry>,
+}
+
+#[derive(Serialize)]
+struct ConfigurationEntry {
+    offset: usize,
+    bytes: Bytes,
+    key: usize,
+    value: Bytes,
+}
+
+#[derive(Serialize)]
+struct Metadata {
+    offset: usize,
+    bytes: Bytes,
+    record_count: usize,
+    byte_size: usize,
+    records: Vec<Record>,
+}
+
+#[derive(Serialize)]
+struct Record {
+    offset: usize,
+    bytes: Bytes,
+    #[serde(flatten)]
+    record: RecordKind,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum RecordKind {
+    Name { name: String },
+    VariableName { identifier: usize, name: String },
+    Reserved { identifier: usize },
+    Unknown { identifier: usize },
+}
+
+#[derive(Serialize)]
+struct Node {
+    offset: usize,
+    /// Bytes of the node itself, without the bytes of its children
+    bytes: Bytes,
+    #[serde(rename = "type")]
+    token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<Node>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Value {
+    Variable {
+        identifier: usize,
+        name: Option<String>,
+    },
+    Signed(i64),
+    Unsigned(u64),
+    Float(f64),
+}
+
+/// Raw bytes, serialised as a hexadecimal string
+struct Bytes(Vec<u8>);
+
+impl Serialize for Bytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.hex(""))
+    }
+}
+
+impl Bytes {
+    fn hex(&self, separator: &str) -> String {
+        self.0
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+}
+
+/// Reader over the file contents, keeping track of the offset
+struct Cursor<'a> {
+    file: &'a [u8],
+    remaining: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn offset(&self) -> usize {
+        self.file.len() - self.remaining.len()
+    }
+
+    fn bytes_from(&self, offset: usize) -> Bytes {
+        Bytes(self.file[offset..self.offset()].to_vec())
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut buffer = [0u8; N];
+        self.remaining
+            .read_exact(&mut buffer)
+            .with_context(|| format!("Reading {} bytes at offset {}.", N, self.offset()))?;
+        Ok(buffer)
+    }
+
+    fn read_vle(&mut self) -> Result<VariableLengthEnum> {
+        let offset = self.offset();
+        VariableLengthEnum::read_from(&mut self.remaining, &DEFAULT_CONFIG)
+            .with_context(|| format!("Reading variable length enum at offset {}.", offset))
+    }
+
+    fn read_usize(&mut self) -> Result<usize> {
+        let offset = self.offset();
+        self.read_vle()?
+            .try_into()
+            .with_context(|| format!("Variable length enum at offset {} is too large.", offset))
+    }
+}
+
+impl Inspection {
+    pub(crate) fn read(file: &[u8]) -> Result<Self> {
+        let mut cursor = Cursor {
+            file,
+            remaining: file,
+        };
+
+        let version = cursor.read_usize().context("Reading version.")?;
+        let version = Item {
+            offset: 0,
+            bytes: cursor.bytes_from(0),
+            value: version,
+        };
+        if version.value != 0 {
+            bail!("Unsupported version: {}", version.value);
+        }
+
+        let offset = cursor.offset();
+        let content_type = FileContentTypeToken::read_from(&mut cursor.remaining, &DEFAULT_CONFIG)
+            .context("Reading file content type.")?;
+        let content_type_item = Item {
+            offset,
+            bytes: cursor.bytes_from(offset),
+            value: format!("{:?}", content_type),
+        };
+
+        let (configuration, metadata) = match content_type {
+            FileContentTypeToken::SingleFormula => {
+                let (configuration_item, configuration) = read_configuration(&mut cursor)?;
+                let metadata = read_metadata(&mut cursor, &configuration)?;
+                (Some(configuration_item), Some(metadata))
+            }
+            FileContentTypeToken::RawFormula => (None, None),
+            _ => bail!("Unsupported file content type: {:?}", content_type),
+        };
+
+        let variable_names: BTreeMap<usize, String> = metadata
+            .iter()
+            .flat_map(|metadata| metadata.records.iter())
+            .filter_map(|record| match &record.record {
+                RecordKind::VariableName { identifier, name } => Some((*identifier, name.clone())),
+                _ => None,
+            })
+            .collect();
+
+        let expression = read_node(&mut cursor, &variable_names)?;
+
+        let trailing = match cursor.remaining.len() {
+            0 => None,
+            length => {
+                let offset = cursor.offset();
+                cursor.remaining = &[];
+                Some(Item {
+                    offset,
+                    bytes: cursor.bytes_from(offset),
+                    value: length,
+                })
+            }
+        };
+
+        Ok(Self {
+            version,
+            content_type: content_type_item,
+            configuration,
+            metadata,
+            expression,
+            trailing,
+        })
+    }
+
+    pub(crate) fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Renders the inspection as a listing of the bytes of each part next to its description
+    pub(crate) fn to_text(&self) -> String {
+        let mut rows: Vec<(usize, &Bytes, String)> = Vec::new();
+
+        rows.push((
+            self.version.offset,
+            &self.version.bytes,
+            format!("version {}", self.version.value),
+        ));
+        rows.push((
+            self.content_type.offset,
+            &self.content_type.bytes,
+            format!("content type {}", self.content_type.value),
+        ));
+
+        if let Some(configuration) = &self.configuration {
+            rows.push((
+                configuration.offset,
+                &configuration.bytes,
+                format!("configuration, {} entries", configuration.entries.len()),
+            ));
+            for entry in configuration.entries.iter() {
+                rows.push((
+                    entry.offset,
+                    &entry.bytes,
+                    format!(
+                        "  key {:#x} (unknown), value {}",
+                        entry.key,
+                        entry.value.hex(" ")
+                    ),
+                ));
+            }
+        }
+
+        if let Some(metadata) = &self.metadata {
+            rows.push((
+                metadata.offset,
+                &metadata.bytes,
+                format!(
+                    "metadata, {} records, {} bytes",
+                    metadata.record_count, metadata.byte_size
+                ),
+            ));
+            for record in metadata.records.iter() {
+                let description = match &record.record {
+                    RecordKind::Name { name } => format!("  Name {:?}", name),
+                    RecordKind::VariableName { identifier, name } => {
+                        format!("  VariableName #{} {:?}", identifier, name)
+                    }
+                    RecordKind::Reserved { identifier } => format!("  Reserved {:#x}", identifier),
+                    RecordKind::Unknown { identifier } => format!("  Unknown {:#x}", identifier),
+                };
+                rows.push((record.offset, &record.bytes, description));
+            }
+        }
+
+        {
+            let mut nodes: Vec<(&Node, usize)> = vec![(&self.expression, 0)];
+            while let Some((node, depth)) = nodes.pop() {
+                let value = match &node.value {
+                    Some(Value::Variable {
+                        identifier,
+                        name: Some(name),
+                    }) => format!(" #{} {:?}", identifier, name),
+                    Some(Value::Variable {
+                        identifier,
+                        name: None,
+                    }) => format!(" #{}", identifier),
+                    Some(Value::Signed(value)) => format!(" {}", value),
+                    Some(Value::Unsigned(value)) => format!(" {}", value),
+                    Some(Value::Float(value)) => format!(" {:?}", value),
+                    None => String::new(),
+                };
+                rows.push((
+                    node.offset,
+                    &node.bytes,
+                    format!("{}{}{}", "  ".repeat(depth), node.token, value),
+                ));
+                nodes.extend(node.children.iter().rev().map(|child| (child, depth + 1)));
+            }
+        }
+
+        if let Some(trailing) = &self.trailing {
+            rows.push((
+                trailing.offset,
+                &trailing.bytes,
+                format!("{} trailing bytes", trailing.value),
+            ));
+        }
+
+        const BYTES_PER_ROW: usize = 8;
+        let mut output = String::new();
+        for (offset, bytes, description) in rows {
+            let mut chunks = bytes.0.chunks(BYTES_PER_ROW);
+            let first = Bytes(chunks.next().unwrap_or_default().to_vec());
+            let _ = writeln!(
+                output,
+                "{:06x}  {:<width$}  {}",
+                offset,
+                first.hex(" "),
+                description,
+                width = BYTES_PER_ROW * 3 - 1
+            );
+            for (index, chunk) in chunks.enumerate() {
+                let _ = writeln!(
+                    output,
+                    "{:06x}  {}",
+                    offset + (index + 1) * BYTES_PER_ROW,
+                    Bytes(chunk.to_vec()).hex(" ")
+                );
+            }
+        }
+        output
+    }
+}
+
+fn read_configuration(cursor: &mut Cursor) -> Result<(Configuration, OverridableConfig)> {
+    let offset = cursor.offset();
+
+    // Read the entries from a copy of the cursor to show them, fef skips entries it doesn't know
+    let mut entries_cursor = Cursor {
+        file: cursor.file,
+        remaining: cursor.remaining,
+    };
+    let entry_count = entries_cursor
+        .read_usize()
+        .context("Reading configuration entry count.")?;
+    let mut entries = Vec::with_capacity(entry_count);
+    for _ in 0..entry_count {
+        let entry_offset = entries_cursor.offset();
+        let key = entries_cursor
+            .read_usize()
+            .context("Reading configuration key.")?;
+        // Keys up to 0x7F are enum configurations with a single value, others are prefixed by their length
+        let value_offset = entries_cursor.offset();
+        if key <= 0x7F {
+            entries_cursor.read_vle()?;
+        } else {
+            let length = entries_cursor.read_usize()?;
+            if entries_cursor.remaining.len() < length {
+                bail!(
+                    "Configuration entry at offset {} is truncated.",
+                    entry_offset
+                );
+            }
+            entries_cursor.remaining = &entries_cursor.remaining[length..];
+        }
+        entries.push(ConfigurationEntry {
+            offset: entry_offset,
+            bytes: entries_cursor.bytes_from(entry_offset),
+            key,
+            value: entries_cursor.bytes_from(value_offset),
+        });
+    }
+
+    let configuration = OverridableConfig::read_from(&mut cursor.remaining, &DEFAULT_CONFIG)
+        .context("Reading configuration.")?;
+
+    // Only the entry count belongs to the configuration row, the entries have their own rows
+    let header_end = entries
+        .first()
+        .map(|entry| entry.offset)
+        .unwrap_or(cursor.offset());
+    Ok((
+        Configuration {
+            offset,
+            bytes: Bytes(cursor.file[offset..header_end].to_vec()),
+            entries,
+        },
+        configuration,
+    ))
+}
+
+fn read_metadata(cursor: &mut Cursor, configuration: &OverridableConfig) -> Result<Metadata> {
+    let offset = cursor.offset();
+    let header = MetadataHeader::read_from(&mut cursor.remaining, configuration)
+        .context("Reading metadata header.")?;
+    let bytes = cursor.bytes_from(offset);
+
+    let mut records = Vec::with_capacity(header.record_count());
+    for _ in 0..header.record_count() {
+        let record_offset = cursor.offset();
+        let record = MetadataRecord::read_from(&mut cursor.remaining, configuration)
+            .with_context(|| format!("Reading metadata record at offset {}.", record_offset))?;
+        let record_bytes = cursor.bytes_from(record_offset);
+
+        // The identifier of the record is the first value of the record
+        let identifier = Cursor {
+            file: &record_bytes.0,
+            remaining: &record_bytes.0,
+        }
+        .read_usize()?;
+
+        let record = match record {
+            MetadataRecord::Name(record) => RecordKind::Name {
+                name: record.name().to_string(),
+            },
+            MetadataRecord::VariableName(record) => RecordKind::VariableName {
+                identifier: record
+                    .variable_identifier()
+                    .clone()
+                    .try_into()
+                    .context("Variable identifier is too large.")?,
+                name: record.name().to_string(),
+            },
+            MetadataRecord::Reserved(_) => RecordKind::Reserved { identifier },
+            _ => RecordKind::Unknown { identifier },
+        };
+        records.push(Record {
+            offset: record_offset,
+            bytes: record_bytes,
+            record,
+        });
+    }
+
+    Ok(Metadata {
+        offset,
+        bytes,
+        record_count: header.record_count(),
+        byte_size: header.byte_size(),
+        records,
+    })
+}
+
+fn read_node(cursor: &mut Cursor, variable_names: &BTreeMap<usize, String>) -> Result<Node> {
+    let offset = cursor.offset();
+    let token = ExprToken::read_from(&mut cursor.remaining, &DEFAULT_CONFIG)
+        .with_context(|| format!("Reading expression token at offset {}.", offset))?;
+
+    let (value, child_count) = match token {
+        ExprToken::Variable => {
+            let identifier = cursor.read_usize()?;
+            let name = variable_names.get(&identifier).cloned();
+            (Some(Value::Variable { identifier, name }), 0)
+        }
+        ExprToken::SignedIntLiteral8 => (
+            Some(Value::Signed(
+                i8::from_be_bytes(cursor.read_array()?).into(),
+            )),
+            0,
+        ),
+        ExprToken::SignedIntLiteral16 => (
+            Some(Value::Signed(
+                i16::from_be_bytes(cursor.read_array()?).into(),
+            )),
+            0,
+        ),
+        ExprToken::SignedIntLiteral32 => (
+            Some(Value::Signed(
+                i32::from_be_bytes(cursor.read_array()?).into(),
+            )),
+            0,
+        ),
+        ExprToken::SignedIntLiteral64 => (
+            Some(Value::Signed(i64::from_be_bytes(cursor.read_array()?))),
+            0,
+        ),
+        ExprToken::UnsignedIntLiteral8 => (
+            Some(Value::Unsigned(
+                u8::from_be_bytes(cursor.read_array()?).into(),
+            )),
+            0,
+        ),
+        ExprToken::UnsignedIntLiteral16 => (
+            Some(Value::Unsigned(
+                u16::from_be_bytes(cursor.read_array()?).into(),
+            )),
+            0,
+        ),
+        ExprToken::UnsignedIntLiteral32 => (
+            Some(Value::Unsigned(
+                u32::from_be_bytes(cursor.read_array()?).into(),
+            )),
+            0,
+        ),
+        ExprToken::UnsignedIntLiteral64 => (
+            Some(Value::Unsigned(u64::from_be_bytes(cursor.read_array()?))),
+            0,
+        ),
+        ExprToken::BinaryFloatLiteral32 => (
+            Some(Value::Float(
+                f32::from_be_bytes(cursor.read_array()?).into(),
+            )),
+            0,
+        ),
+        ExprToken::BinaryFloatLiteral64 => (
+            Some(Value::Float(f64::from_be_bytes(cursor.read_array()?))),
+            0,
+        ),
+        ExprToken::TrueLiteral | ExprToken::FalseLiteral => (None, 0),
+        ExprToken::Addition
+        | ExprToken::Subtraction
+        | ExprToken::Multiplication
+        | ExprToken::Division
+        | ExprToken::IntDivision
+        | ExprToken::Modulo
+        | ExprToken::Power
+        | ExprToken::Root
+        | ExprToken::IntRoot => (None, 2),
+        ExprToken::Negation
+        | ExprToken::Square
+        | ExprToken::Cube
+        | ExprToken::SquareRoot
+        | ExprToken::CubeRoot
+        | ExprToken::Reciprocal => (None, 1),
+        _ => bail!(
+            "Unsupported expression token {} at offset {}",
+            token,
+            offset
+        ),
+    };
+
+    let bytes = cursor.bytes_from(offset);
+    let children = (0..child_count)
+        .map(|_| read_node(cursor, variable_names))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Node {
+        offset,
+        bytes,
+        token: token.to_string(),
+        value,
+        children,
+    })
+}
@@ -1,56 +1,680 @@
-use std::fmt::Display;
+use std::{collections::BTreeMap, convert::Infallible, fmt::Display, iter::Peekable};
 
 use anyhow::{anyhow, Result};
 use fef::v0::{
     expr::{
-        Expr, ExprAddition, ExprBinaryFloat64Literal, ExprDivision, ExprIntDivision, ExprModulo,
-        ExprMultiplication, ExprNegation, ExprPower, ExprSignedIntLiteral, ExprSubtraction,
-        ExprTree, ExprUnsignedIntLiteral, ExprVariable,
+        error::ComposeError,
+        traits::{Composer, ExprObj},
+        Expr, ExprAddition, ExprBinaryFloat32Literal, ExprBinaryFloat64Literal, ExprDivision,
+        ExprFalseLiteral, ExprIntDivision, ExprModulo, ExprMultiplication, ExprNegation, ExprPower,
+        ExprSignedIntLiteral, ExprSubtraction, ExprTree, ExprTrueLiteral, ExprUnsignedIntLiteral,
+        ExprVariable,
     },
     raw::VariableLengthEnum,
 };
 
-use crate::parser::{ParsedToken, Token};
+use crate::{
+    compose_tree::{compose_tree, infallible},
+    constants::Constant,
+    diagnostic::{Code, Diagnostic, Span},
+    functions::Function,
+    parser::{quote_identifier, written_identifier, ParsedToken, Token},
+};
+
+/// Options of parsing a formula that change the resulting expression tree
+#[derive(Debug, Clone)]
+pub(crate) struct ParseOptions {
+    pub(crate) literals: LiteralEncoding,
+    /// Whether negated number literals are written as negative literals instead of negations
+    pub(crate) fold_negative_literals: bool,
+    pub(crate) implicit_multiplication: ImplicitMultiplication,
+    /// Whether names of built-in constants such as `pi` are read as the constants instead of variables
+    pub(crate) builtin_constants: bool,
+    /// Names of variables declared outside of the formula, which get the first identifiers in their order
+    pub(crate) variables: Vec<String>,
+    pub(crate) variable_order: VariableOrder,
+    /// Whether variables that were not declared are an error
+    pub(crate) strict_variables: bool,
+}
+
+/// How number literals are encoded in the expression tree
+///
+/// Integer literals up to `i64::MAX` always get the smallest integer encoding, which is exact.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, clap::ValueEnum)]
+pub(crate) enum LiteralEncoding {
+    /// Floats are written as 32-bit floats if that is exact, as 64-bit floats otherwise
+    #[default]
+    Exact,
+    /// Floats are always written as 64-bit floats
+    F64,
+    /// Floats are written as 32-bit floats, with a warning for each float that loses precision
+    Lossy,
+}
+
+/// Whether adjacent expressions such as `2x` or `(a)(b)` are multiplied
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, clap::ValueEnum)]
+pub(crate) enum ImplicitMultiplication {
+    /// Adjacent expressions are an error
+    #[default]
+    Off,
+    /// Adjacent expressions are multiplied
+    On,
+    /// Adjacent expressions are multiplied, with a warning for each implicit multiplication
+    Warn,
+}
+
+/// Order in which variables that were not declared get their identifiers, after the declared variables
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, clap::ValueEnum)]
+pub(crate) enum VariableOrder {
+    /// Variables are numbered in the order of their first use in the formula
+    #[default]
+    Appearance,
+    /// Variables are numbered in the alphabetical order of their names
+    Alphabetical,
+}
 
+/// State shared by all groups of a formula while it is parsed
+struct ParseState<'a> {
+    variables: &'a mut Vec<String>,
+    options: &'a ParseOptions,
+    warnings: &'a mut Vec<Diagnostic>,
+    bindings: BTreeMap<String, Binding>,
+    /// Names of the bindings being parsed, the innermost last
+    resolving: Vec<String>,
+    /// Spans of the names of variables declared in the formula
+    declarations: BTreeMap<String, Span>,
+}
+
+/// A `let` binding of a name to an expression, which is inlined wherever the name is used
+struct Binding {
+    name_span: Span,
+    /// Span of the `=`, where a missing expression is reported
+    equals_span: Span,
+    body: Vec<ParsedToken>,
+    /// The expression of the binding, once it was parsed
+    tree: Option<ExprTree>,
+}
+
+/// Parses the tokens of a formula into an expression tree
+///
+/// The formula can start with `let` bindings, which are inlined into the expression, and `var` declarations,
+/// each ended by a line break or `;`. Names of the free variables are pushed to `variables` in the order of
+/// their identifiers, declared variables first, and problems that don't prevent parsing are pushed to
+/// `warnings`.
 pub(crate) fn into_expr_tree(
     tokens: &mut impl Iterator<Item = Result<ParsedToken>>,
     variables: &mut Vec<String>,
-    in_parenthesis: bool,
+    options: &ParseOptions,
+    warnings: &mut Vec<Diagnostic>,
 ) -> Result<ExprTree> {
-    // Convert the tokens into symbols
-    let mut symbols = into_symbols(tokens, variables, in_parenthesis)?
+    let mut tokens = tokens.peekable();
+    let statements = read_statements(&mut tokens)?;
+    let mut state = ParseState {
+        variables,
+        options,
+        warnings,
+        bindings: statements.bindings,
+        resolving: Vec::new(),
+        declarations: BTreeMap::new(),
+    };
+    declare_variables(&mut state, statements.declarations)?;
+    let declared_count = state.variables.len();
+    warn_about_shadowed_constants(&mut state);
+
+    // Line breaks only end bindings
+    let mut tokens = statements
+        .expression_start
+        .map(Ok)
         .into_iter()
-        .map(|s| Some(s))
-        .collect::<Vec<_>>();
+        .chain(tokens)
+        .filter(|token| {
+            !matches!(
+                token,
+                Ok(ParsedToken {
+                    token: Token::Newline,
+                    ..
+                })
+            )
+        })
+        .peekable();
+    let expr = match parse_group(&mut tokens, &mut state, Group::TopLevel)? {
+        (Some(expr), _) => expr,
+        (None, closing) => {
+            let end = closing.end().max(statements.end);
+            let error = Diagnostic::error(
+                Code::ExpectedExpression,
+                "expected expression, found end of input",
+                Span::new(end, end),
+            );
+            let error = if state.bindings.is_empty() && state.declarations.is_empty() {
+                error
+            } else {
+                error.with_help(
+                    "a formula with `let` bindings or `var` declarations ends with the expression of the formula",
+                )
+            };
+            return Err(error.into());
+        }
+    };
+
+    check_unused_bindings(&mut state)?;
+
+    match options.variable_order {
+        VariableOrder::Appearance => Ok(expr),
+        VariableOrder::Alphabetical => Ok(sort_variables(&expr, state.variables, declared_count)),
+    }
+}
+
+/// The `let` bindings and `var` declarations before the expression of a formula
+struct Statements {
+    bindings: BTreeMap<String, Binding>,
+    /// Declared variables in the order of their declarations
+    declarations: Vec<Declaration>,
+    /// End of the last token of the statements
+    end: usize,
+    /// A `let` or `var` identifier read while looking for a statement, which starts the expression instead
+    expression_start: Option<ParsedToken>,
+}
+
+/// A name declared as a variable with `var`
+struct Declaration {
+    name: String,
+    span: Span,
+    /// Whether the name is written in quotes, so it is never a built-in constant
+    quoted: bool,
+}
+
+/// Reads the `let` bindings and `var` declarations at the start of a formula, up to the first token of its
+/// expression
+///
+/// `let` and `var` are keywords only when a name follows them, otherwise they are names of variables, so
+/// `let + 1` is a formula with the variable `let`.
+fn read_statements<I: Iterator<Item = Result<ParsedToken>>>(
+    tokens: &mut Peekable<I>,
+) -> Result<Statements> {
+    let mut statements = Statements {
+        bindings: BTreeMap::new(),
+        declarations: Vec::new(),
+        end: 0,
+        expression_start: None,
+    };
+    loop {
+        match tokens.peek() {
+            Some(Ok(ParsedToken {
+                token: Token::Newline | Token::Semicolon,
+                ..
+            })) => {
+                statements.end = tokens.next().expect("Token peeked")?.end;
+                continue;
+            }
+            Some(Ok(ParsedToken {
+                token: Token::Identifier(keyword),
+                ..
+            })) if keyword == "let" || keyword == "var" => {}
+            _ => return Ok(statements),
+        }
+
+        let keyword_token = tokens.next().expect("Token peeked")?;
+        let name_follows = matches!(
+            tokens.peek(),
+            Some(Ok(ParsedToken {
+                token: Token::Identifier(_) | Token::QuotedIdentifier(_),
+                ..
+            }))
+        );
+        if !name_follows {
+            statements.expression_start = Some(keyword_token);
+            return Ok(statements);
+        }
+
+        match &keyword_token.token {
+            Token::Identifier(keyword) if keyword == "let" => {
+                let (name, binding, binding_end) = read_binding(tokens, keyword_token)?;
+                if let Some(first) = statements.bindings.get(&name) {
+                    return Err(Diagnostic::error(
+                        Code::DuplicateBinding,
+                        format!("`{}` is bound more than once", name),
+                        binding.name_span,
+                    )
+                    .with_label("bound again here")
+                    .with_secondary(first.name_span, "first bound here")
+                    .into());
+                }
+                statements.bindings.insert(name, binding);
+                statements.end = binding_end;
+            }
+            _ => {
+                statements.end =
+                    read_declaration(tokens, keyword_token, &mut statements.declarations)?;
+            }
+        }
+    }
+}
+
+/// Reads a `let` binding after its `let` keyword up to the line break or `;` ending it outside of parentheses
+///
+/// Returns the name, the binding and the end of its last token.
+fn read_binding<I: Iterator<Item = Result<ParsedToken>>>(
+    tokens: &mut Peekable<I>,
+    let_token: ParsedToken,
+) -> Result<(String, Binding, usize)> {
+    let (name, name_span) = match tokens.next().transpose()? {
+        Some(ParsedToken {
+            token: Token::Identifier(name) | Token::QuotedIdentifier(name),
+            start,
+            end,
+        }) => (name, Span::new(start, end)),
+        other => {
+            return Err(Diagnostic::error(
+                Code::InvalidBinding,
+                "expected name of the binding after `let`",
+                found_span(other, let_token.end),
+            )
+            .with_label("expected a name")
+            .with_secondary(let_token.span(), "binding starts here")
+            .into())
+        }
+    };
+
+    let equals_span = match tokens.next().transpose()? {
+        Some(ParsedToken {
+            token: Token::Equals,
+            start,
+            end,
+        }) => Span::new(start, end),
+        other => {
+            return Err(Diagnostic::error(
+                Code::InvalidBinding,
+                format!("expected `=` after the name of the binding `{}`", name),
+                found_span(other, name_span.end),
+            )
+            .with_label("expected `=`")
+            .with_secondary(name_span, "name of the binding")
+            .into())
+        }
+    };
+
+    let mut body = Vec::new();
+    let mut depth: usize = 0;
+    let mut end = equals_span.end;
+    loop {
+        match tokens.peek() {
+            None => break,
+            Some(Ok(ParsedToken {
+                token: Token::Newline | Token::Semicolon,
+                ..
+            })) if depth == 0 => break,
+            Some(_) => {}
+        }
+        let token = tokens.next().expect("Token peeked")?;
+        match token.token {
+            Token::LeftParenthesis => depth += 1,
+            Token::RightParenthesis => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        end = token.end;
+        if token.token != Token::Newline {
+            body.push(token);
+        }
+    }
+
+    let binding = Binding {
+        name_span,
+        equals_span,
+        body,
+        tree: None,
+    };
+    Ok((name, binding, end))
+}
+
+/// Reads a `var` declaration of comma separated names after its `var` keyword up to the line break or `;`
+/// ending it
+///
+/// Returns the end of its last token.
+fn read_declaration<I: Iterator<Item = Result<ParsedToken>>>(
+    tokens: &mut Peekable<I>,
+    var_token: ParsedToken,
+    declarations: &mut Vec<Declaration>,
+) -> Result<usize> {
+    // The `var` keyword or the last comma, after which a name is expected
+    let (mut previous, mut previous_end) = ("var", var_token.end);
+    loop {
+        match tokens.next().transpose()? {
+            Some(ParsedToken {
+                token: Token::Identifier(name),
+                start,
+                end,
+            }) => declarations.push(Declaration {
+                name,
+                span: Span::new(start, end),
+                quoted: false,
+            }),
+            Some(ParsedToken {
+                token: Token::QuotedIdentifier(name),
+                start,
+                end,
+            }) => declarations.push(Declaration {
+                name,
+                span: Span::new(start, end),
+                quoted: true,
+            }),
+            other => {
+                return Err(Diagnostic::error(
+                    Code::InvalidDeclaration,
+                    format!("expected name of a variable after `{}`", previous),
+                    found_span(other, previous_end),
+                )
+                .with_label("expected a name")
+                .with_secondary(var_token.span(), "declaration starts here")
+                .into())
+            }
+        }
+        let name_end = declarations.last().expect("Name pushed").span.end;
+
+        match tokens.peek() {
+            None
+            | Some(Ok(ParsedToken {
+                token: Token::Newline | Token::Semicolon,
+                ..
+            })) => return Ok(name_end),
+            Some(_) => {}
+        }
+        match tokens.next().transpose()? {
+            Some(ParsedToken {
+                token: Token::Comma,
+                end,
+                ..
+            }) => (previous, previous_end) = (",", end),
+            other => {
+                return Err(Diagnostic::error(
+                    Code::InvalidDeclaration,
+                    "expected `,` or the end of the declaration",
+                    found_span(other, name_end),
+                )
+                .with_label("expected `,`")
+                .with_secondary(var_token.span(), "declaration starts here")
+                .with_help("a declaration ends at the end of its line or at a `;`")
+                .into())
+            }
+        }
+    }
+}
+
+/// Span of the token found instead of an expected one, or the end of the input after `end`
+fn found_span(found: Option<ParsedToken>, end: usize) -> Span {
+    match found {
+        Some(token) => token.span(),
+        None => Span::new(end, end),
+    }
+}
+
+/// Gives the variables declared with the options and in the formula the first identifiers, in this order
+///
+/// Names of built-in constants can't be declared with the options, as they would still be read as the
+/// constants. Declaring them unquoted in the formula is warned about for the same reason.
+fn declare_variables(state: &mut ParseState<'_>, declarations: Vec<Declaration>) -> Result<()> {
+    for name in &state.options.variables {
+        if state.variables.contains(name) {
+            return Err(anyhow!("Variable '{}' is declared more than once.", name));
+        }
+        if state.options.builtin_constants && Constant::from_name(name).is_some() {
+            return Err(anyhow!(
+                "Variable '{}' has the name of a built-in constant, which the formula reads as the constant. Use --no-builtin-constants to read it as a variable.",
+                name
+            ));
+        }
+        state.variables.push(name.clone());
+    }
+
+    for Declaration { name, span, quoted } in declarations {
+        if let Some(binding) = state.bindings.get(&name) {
+            return Err(Diagnostic::error(
+                Code::InvalidDeclaration,
+                format!("`{}` is declared as a variable and bound", name),
+                span,
+            )
+            .with_label("declared as a variable here")
+            .with_secondary(binding.name_span, "bound here")
+            .into());
+        }
+        if state.variables.contains(&name) {
+            let error = Diagnostic::error(
+                Code::DuplicateDeclaration,
+                format!("variable `{}` is declared more than once", name),
+                span,
+            )
+            .with_label("declared again here");
+            let error = match state.declarations.get(&name) {
+                Some(first) => error.with_secondary(*first, "first declared here"),
+                None => error.with_help("the variable is already declared with `--variables`"),
+            };
+            return Err(error.into());
+        }
+        let constant = Constant::from_name(&name).filter(|_| state.options.builtin_constants);
+        if let (Some(constant), false) = (constant, quoted) {
+            state.warnings.push(
+                Diagnostic::warning(
+                    Code::ShadowedConstant,
+                    format!("variable `{}` has the name of a built-in constant", name),
+                    span,
+                )
+                .with_label(format!(
+                    "`{}` in the expression is still the constant {}",
+                    name,
+                    constant.value()
+                ))
+                .with_help(format!(
+                    "refer to the variable as `{}`",
+                    quote_identifier(&name)
+                )),
+            );
+        }
+        state.variables.push(name.clone());
+        state.declarations.insert(name, span);
+    }
+    Ok(())
+}
+
+/// Warns about bindings with the names of built-in constants, which are used instead of the constants
+fn warn_about_shadowed_constants(state: &mut ParseState<'_>) {
+    if !state.options.builtin_constants {
+        return;
+    }
+    let mut shadowing: Vec<(Span, Constant)> = state
+        .bindings
+        .iter()
+        .filter_map(|(name, binding)| Some((binding.name_span, Constant::from_name(name)?)))
+        .collect();
+    shadowing.sort_by_key(|(span, _)| span.start);
+    for (span, constant) in shadowing {
+        state.warnings.push(
+            Diagnostic::warning(
+                Code::ShadowedConstant,
+                format!("binding `{}` shadows the built-in constant", constant),
+                span,
+            )
+            .with_label(format!("used instead of the constant {}", constant.value())),
+        );
+    }
+}
+
+/// The expression of the binding with the name, which is parsed when it is used for the first time
+fn resolve_binding(name: &str, span: Span, state: &mut ParseState<'_>) -> Result<ExprTree> {
+    let binding = state.bindings.get(name).expect("Binding exists");
+    if let Some(tree) = &binding.tree {
+        return Ok(tree.clone());
+    }
+
+    if let Some(position) = state
+        .resolving
+        .iter()
+        .position(|resolving| resolving == name)
+    {
+        let cycle = &state.resolving[position..];
+        let mut error = Diagnostic::error(
+            Code::CyclicBinding,
+            format!("binding `{}` depends on itself", name),
+            span,
+        )
+        .with_label(format!("`{}` is used while it is being defined", name))
+        .with_help(format!(
+            "the bindings form a cycle: {} -> {}",
+            cycle.join(" -> "),
+            name
+        ));
+        for bound in cycle {
+            error = error.with_secondary(
+                state.bindings[bound].name_span,
+                format!("`{}` is bound here", bound),
+            );
+        }
+        return Err(error.into());
+    }
+
+    let body = binding.body.clone();
+    let equals_span = binding.equals_span;
+    state.resolving.push(name.to_string());
+    let mut tokens = body.into_iter().map(Ok).peekable();
+    let (tree, _) = parse_group(&mut tokens, state, Group::TopLevel)?;
+    state.resolving.pop();
+
+    let tree = tree.ok_or_else(|| {
+        Diagnostic::error(
+            Code::InvalidBinding,
+            format!("expected expression of the binding `{}`", name),
+            equals_span,
+        )
+        .with_label("expected an expression after this")
+    })?;
+    state.bindings.get_mut(name).expect("Binding exists").tree = Some(tree.clone());
+    Ok(tree)
+}
 
-    // Removes all unary operators by composing them with their operands
-    compose_unary_expressions(&mut symbols)?;
+/// Warns about bindings the expression doesn't use, after checking them for errors
+fn check_unused_bindings(state: &mut ParseState<'_>) -> Result<()> {
+    let mut unused: Vec<(String, Span)> = state
+        .bindings
+        .iter()
+        .filter(|(_, binding)| binding.tree.is_none())
+        .map(|(name, binding)| (name.clone(), binding.name_span))
+        .collect();
+    unused.sort_by_key(|(_, span)| span.start);
+
+    // Variables used only by unused bindings are not variables of the formula
+    let variable_count = state.variables.len();
+    for (name, span) in unused {
+        state.warnings.push(
+            Diagnostic::warning(
+                Code::UnusedBinding,
+                format!("unused binding `{}`", name),
+                span,
+            )
+            .with_label("never used in the expression"),
+        );
+        resolve_binding(&name, span, state)?;
+    }
+    state.variables.truncate(variable_count);
+    Ok(())
+}
+
+/// Parses tokens up to the closing delimiter of the group into an expression tree
+///
+/// Returns `None` as the expression if the group is empty.
+fn parse_group<I: Iterator<Item = Result<ParsedToken>>>(
+    tokens: &mut Peekable<I>,
+    state: &mut ParseState<'_>,
+    group: Group,
+) -> Result<(Option<ExprTree>, Closing)> {
+    // Convert the tokens into symbols
+    let (symbols, closing) = into_symbols(tokens, state, group)?;
+    if symbols.is_empty() {
+        return Ok((None, closing));
+    }
+    let mut symbols = symbols;
+
+    // Multiplies adjacent operands if implicit multiplication is enabled
+    insert_implicit_multiplications(&mut symbols, state);
+    // Distinguishes unary operators from binary operators
+    mark_unary_operators(&mut symbols)?;
     // Covert infix notation to postfix notation
-    let clean_symbols = symbols.into_iter().map(|o| o.expect("Cleaned")).collect();
+    let postfix_symbols = shunting_yard_algorithm(symbols)?;
 
-    let postfix_symbols = shunting_yard_algorithm(clean_symbols)?;
+    let expr = compose_expressions(postfix_symbols, state.options)?;
 
-    let expr = compose_binary_expressions(postfix_symbols.into_iter())?;
+    Ok((Some(expr), closing))
+}
 
-    Ok(expr)
+/// Parses the comma separated arguments of a function call, after the opening parenthesis was consumed
+///
+/// Returns the arguments and the index after the closing parenthesis.
+fn parse_arguments<I: Iterator<Item = Result<ParsedToken>>>(
+    tokens: &mut Peekable<I>,
+    state: &mut ParseState<'_>,
+    function: Function,
+    start: usize,
+) -> Result<(Vec<ExprTree>, usize)> {
+    let mut arguments: Vec<ExprTree> = Vec::new();
+    loop {
+        let (argument, closing) = parse_group(tokens, state, Group::Arguments)?;
+        match (argument, closing) {
+            (None, Closing::RightParenthesis { end }) if arguments.is_empty() => {
+                return Ok((arguments, end));
+            }
+            (None, Closing::RightParenthesis { end } | Closing::Comma { end }) => {
+                return Err(Diagnostic::error(
+                    Code::ExpectedExpression,
+                    format!("expected argument of function `{}`", function),
+                    Span::new(end - 1, end),
+                )
+                .with_label("expected an argument before this")
+                .into());
+            }
+            (Some(argument), Closing::Comma { end: _ }) => arguments.push(argument),
+            (Some(argument), Closing::RightParenthesis { end }) => {
+                arguments.push(argument);
+                return Ok((arguments, end));
+            }
+            (_, Closing::End { end }) => {
+                return Err(Diagnostic::error(
+                    Code::UnclosedParenthesis,
+                    format!("unclosed argument list of function `{}`", function),
+                    Span::new(start, start + 1),
+                )
+                .with_label("argument list opened here")
+                .with_secondary(Span::new(end, end), "expected `)` here")
+                .into());
+            }
+        }
+    }
 }
 
 /// Converts a sequence of tokens into a sequence of symbols.
-fn into_symbols(
-    tokens: &mut impl Iterator<Item = Result<ParsedToken>>,
-    variables: &mut Vec<String>,
-    in_parenthesis: bool,
-) -> Result<Vec<ParsedSymbol>> {
+fn into_symbols<I: Iterator<Item = Result<ParsedToken>>>(
+    tokens: &mut Peekable<I>,
+    state: &mut ParseState<'_>,
+    group: Group,
+) -> Result<(Vec<ParsedSymbol>, Closing)> {
     let mut symbols: Vec<ParsedSymbol> = Vec::new();
 
+    let mut input_end = 0;
     while let Some(parsed_token) = tokens.next() {
         let parsed_token = parsed_token?;
+        let token_span = parsed_token.span();
+        let mut start = token_span.start;
+        let mut end = parsed_token.end;
+        input_end = end;
+        let mut literal = false;
 
         let symbol = match parsed_token.token {
             Token::Asterisk => Symbol::Operator(Operator::Asterisk),
             Token::Backslash => Symbol::Operator(Operator::Backslash),
             Token::Caret => Symbol::Operator(Operator::Caret),
-            Token::Comma => Symbol::Operator(Operator::Comma),
+            Token::Comma => {
+                if group == Group::Arguments {
+                    return Ok((symbols, Closing::Comma { end }));
+                }
+                Symbol::Operator(Operator::Comma)
+            }
             Token::DoubleSlash => Symbol::Operator(Operator::DoubleSlash),
             Token::Equals => Symbol::Operator(Operator::Equals),
             Token::GreaterThan => Symbol::Operator(Operator::GreaterThan),
@@ -63,166 +687,510 @@ fn into_symbols(
             Token::Plus => Symbol::Operator(Operator::Plus),
             Token::Slash => Symbol::Operator(Operator::Slash),
             Token::Period => Symbol::Operator(Operator::Period),
+            Token::Radical(degree) => Symbol::Operator(Operator::Radical(degree)),
+            Token::Squared => {
+                let (operand_start, operand) =
+                    superscript(&mut symbols, Function::Square, token_span)?;
+                start = operand_start;
+                operand
+            }
+            Token::Cubed => {
+                let (operand_start, operand) =
+                    superscript(&mut symbols, Function::Cube, token_span)?;
+                start = operand_start;
+                operand
+            }
+            // Line breaks only end bindings
+            Token::Newline => continue,
+            Token::Semicolon => {
+                return Err(
+                    Diagnostic::error(Code::InvalidBinding, "unexpected `;`", token_span)
+                        .with_label("`;` can only end a `let` binding or a `var` declaration")
+                        .into(),
+                )
+            }
             Token::DoubleAsterisk => Symbol::Operator(Operator::DoubleAsterisk),
 
             Token::BoolLiteral(b) => {
-                let number: u64 = if b { 1 } else { 0 };
-                let expr_obj: ExprUnsignedIntLiteral<ExprTree> =
-                    ExprUnsignedIntLiteral::from(number);
-                let expr: Expr<ExprTree> = expr_obj.into();
+                let expr: Expr<ExprTree> = if b {
+                    ExprTrueLiteral::from(()).into()
+                } else {
+                    ExprFalseLiteral::from(()).into()
+                };
                 Symbol::Operand(ExprTree::from(expr))
             }
             Token::FloatLiteral(f) => {
-                let expr_obj = ExprBinaryFloat64Literal::from(f);
-                let expr: Expr<ExprTree> = expr_obj.into();
-                Symbol::Operand(ExprTree::from(expr))
+                literal = true;
+                Symbol::Operand(float_literal(f, token_span, state))
             }
             Token::IntLiteral(i) => {
-                let expr_obj = ExprSignedIntLiteral::from(i);
-                let expr: Expr<ExprTree> = expr_obj.into();
+                literal = true;
+                Symbol::Operand(int_literal(i, token_span, state)?)
+            }
+            Token::Constant(constant) => {
+                literal = true;
+                Symbol::Operand(constant.compose())
+            }
+            Token::SignedIntLiteral(i) => {
+                literal = true;
+                let expr: Expr<ExprTree> = ExprSignedIntLiteral::from(i).into();
                 Symbol::Operand(ExprTree::from(expr))
             }
-            Token::Identifier(name) => {
-                let variable_id = if let Some(id) = variables.iter().position(|v| v == &name) {
-                    id
-                } else {
-                    let id = variables.len();
-                    variables.push(name);
-                    id
-                };
-                let vre = VariableLengthEnum::from(variable_id);
-                let expr: Expr<ExprTree> = ExprVariable::from(vre).into();
+            Token::Float64Literal(f) => {
+                literal = true;
+                let expr: Expr<ExprTree> = ExprBinaryFloat64Literal::from(f).into();
                 Symbol::Operand(ExprTree::from(expr))
             }
+            Token::Identifier(name)
+                if is_call(tokens)
+                    && (state.options.implicit_multiplication == ImplicitMultiplication::Off
+                        || Function::from_name(&name).is_some()) =>
+            {
+                let function = Function::from_name(&name).ok_or_else(|| {
+                    Diagnostic::error(
+                        Code::UnknownFunction,
+                        format!("unknown function `{}`", name),
+                        token_span,
+                    )
+                    .with_label("not a function")
+                    .with_help(format!(
+                        "the available functions are {}",
+                        Function::names().join(", ")
+                    ))
+                })?;
+                let parenthesis = tokens
+                    .next()
+                    .expect("Call checked")
+                    .expect("Call checked")
+                    .start;
+
+                let (arguments, call_end) = parse_arguments(tokens, state, function, parenthesis)?;
+                if arguments.len() != function.arity() {
+                    return Err(Diagnostic::error(
+                        Code::WrongArgumentCount,
+                        format!(
+                            "function `{}` takes {} argument(s) but {} were given",
+                            function,
+                            function.arity(),
+                            arguments.len()
+                        ),
+                        Span::new(token_span.start, call_end),
+                    )
+                    .with_secondary(
+                        token_span,
+                        format!("expects {} argument(s)", function.arity()),
+                    )
+                    .into());
+                }
+                end = call_end;
+                Symbol::Operand(function.compose(arguments))
+            }
+            Token::Identifier(name) | Token::QuotedIdentifier(name)
+                if state.bindings.contains_key(&name) =>
+            {
+                Symbol::Operand(resolve_binding(&name, token_span, state)?)
+            }
+            Token::QuotedIdentifier(name) => Symbol::Operand(variable(name, token_span, state)?),
+            Token::Identifier(name) => match Constant::from_name(&name) {
+                Some(constant) if state.options.builtin_constants => {
+                    literal = true;
+                    Symbol::Operand(constant.compose())
+                }
+                _ => Symbol::Operand(variable(name, token_span, state)?),
+            },
 
             Token::RightParenthesis => {
-                if in_parenthesis {
-                    break;
+                if group == Group::TopLevel {
+                    return Err(Diagnostic::error(
+                        Code::UnexpectedClosingParenthesis,
+                        "unexpected closing parenthesis",
+                        token_span,
+                    )
+                    .with_label("no matching opening parenthesis")
+                    .into());
                 } else {
-                    return Err(anyhow!(
-                        "Unexpected ending parenthesis at {}",
-                        parsed_token.end
-                    ));
+                    return Ok((symbols, Closing::RightParenthesis { end }));
                 }
             }
 
             Token::LeftParenthesis => {
-                let expr = into_expr_tree(tokens, variables, true)?;
-                Symbol::Operand(expr)
+                let (expr, closing) = parse_group(tokens, state, Group::Parenthesis)?;
+                match closing {
+                    Closing::RightParenthesis { end: group_end } => end = group_end,
+                    Closing::End { end: input_end } => {
+                        return Err(Diagnostic::error(
+                            Code::UnclosedParenthesis,
+                            "unclosed parenthesis",
+                            token_span,
+                        )
+                        .with_label("parenthesis opened here")
+                        .with_secondary(Span::new(input_end, input_end), "expected `)` here")
+                        .into())
+                    }
+                    Closing::Comma { .. } => unreachable!("Comma only closes arguments"),
+                }
+                Symbol::Operand(expr.ok_or_else(|| {
+                    Diagnostic::error(
+                        Code::ExpectedExpression,
+                        "expected expression inside parentheses",
+                        Span::new(token_span.start, end),
+                    )
+                })?)
             }
         };
 
         symbols.push(ParsedSymbol {
-            end: parsed_token.end,
-            start: parsed_token.start,
             symbol,
+            start,
+            end,
+            literal,
         });
     }
-    Ok(symbols)
+    Ok((symbols, Closing::End { end: input_end }))
+}
+
+/// The variable with the name, which gets the next identifier when it is used for the first time
+///
+/// Warns when a new variable has the name of a built-in constant that is not available. New variables are
+/// an error with strict variables.
+fn variable(name: String, span: Span, state: &mut ParseState<'_>) -> Result<ExprTree> {
+    let variable_id = if let Some(id) = state.variables.iter().position(|v| v == &name) {
+        id
+    } else {
+        if state.options.strict_variables {
+            return Err(Diagnostic::error(
+                Code::UndeclaredVariable,
+                format!("undeclared variable `{}`", name),
+                span,
+            )
+            .with_label("not declared")
+            .with_help(format!(
+                "declare it with `var {}` before the expression or with `--variables`",
+                written_identifier(&name)
+            ))
+            .into());
+        }
+        let constant = Constant::from_name(&name).filter(|_| !state.options.builtin_constants);
+        if let Some(constant) = constant {
+            state.warnings.push(
+                Diagnostic::warning(
+                    Code::ShadowedConstant,
+                    format!("variable `{}` shadows the built-in constant", name),
+                    span,
+                )
+                .with_label(format!(
+                    "read as a variable instead of the constant {}",
+                    constant.value()
+                ))
+                .with_help("built-in constants are not available with `--no-builtin-constants`"),
+            );
+        }
+        let id = state.variables.len();
+        state.variables.push(name);
+        id
+    };
+    let vre = VariableLengthEnum::from(variable_id);
+    let expr: Expr<ExprTree> = ExprVariable::from(vre).into();
+    Ok(ExprTree::from(expr))
 }
 
-/// Treats all operators in place of unary operators as unary operators with the same precedence and composes them with their operands, if possible.
-fn compose_unary_expressions(symbols: &mut Vec<Option<ParsedSymbol>>) -> Result<()> {
+/// Renumbers the variables after the first `declared_count` in the alphabetical order of their names
+fn sort_variables(tree: &ExprTree, variables: &mut [String], declared_count: usize) -> ExprTree {
+    let mut sorted: Vec<(usize, String)> = variables[declared_count..]
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(index, name)| (declared_count + index, name))
+        .collect();
+    sorted.sort_by(|(_, a), (_, b)| a.cmp(b));
+
+    let mut renumbering = Renumbering {
+        identifiers: BTreeMap::new(),
+    };
+    for (new_id, (old_id, name)) in (declared_count..).zip(sorted) {
+        renumbering.identifiers.insert(old_id.into(), new_id.into());
+        variables[new_id] = name;
+    }
+    compose_tree(tree, &mut renumbering).expect("Renumbering doesn't fail")
+}
+
+/// Rebuilds an expression tree with new identifiers of its variables
+struct Renumbering {
+    identifiers: BTreeMap<VariableLengthEnum, VariableLengthEnum>,
+}
+
+impl Composer<ExprTree> for Renumbering {
+    type Error = Infallible;
+
+    fn compose_default<E: ExprObj<ExprTree>>(
+        &mut self,
+        expr: E,
+    ) -> Result<ExprTree, ComposeError<Self::Error>> {
+        Ok(ExprTree::from(expr.into()))
+    }
+
+    fn compose_variable(
+        &mut self,
+        expr: ExprVariable<ExprTree>,
+    ) -> Result<ExprTree, ComposeError<Self::Error>> {
+        let identifier: VariableLengthEnum = expr.into();
+        let identifier = self
+            .identifiers
+            .get(&identifier)
+            .cloned()
+            .unwrap_or(identifier);
+        let expr: Expr<ExprTree> = ExprVariable::from(identifier).into();
+        Ok(ExprTree::from(expr))
+    }
+}
+
+/// Encodes an integer literal with the smallest exact encoding
+///
+/// Integers above `u32::MAX` use the signed encoding, which is as small as the unsigned one. The fef crate
+/// writes unsigned 64-bit literals with a token it can't read back, so integers above `i64::MAX` are written
+/// as 64-bit floats with a warning, whatever the literal encoding.
+fn int_literal(value: u64, span: Span, state: &mut ParseState<'_>) -> Result<ExprTree> {
+    let expr: Expr<ExprTree> = if value <= u64::from(u32::MAX) {
+        ExprUnsignedIntLiteral::from(value).into()
+    } else if let Ok(value) = i64::try_from(value) {
+        ExprSignedIntLiteral::from(value).into()
+    } else {
+        let float = value as f64;
+        let message = if float as u128 == u128::from(value) {
+            "integer literal is written as a float"
+        } else {
+            "integer literal loses precision"
+        };
+        state.warnings.push(
+            Diagnostic::warning(Code::LossyLiteral, message, span)
+                .with_label(format!("written as the float {:e}", float))
+                .with_help(format!(
+                    "integer literals can be at most {}, larger ones are written as floats",
+                    i64::MAX
+                )),
+        );
+        ExprBinaryFloat64Literal::from(float).into()
+    };
+    Ok(ExprTree::from(expr))
+}
+
+/// Encodes a float literal as the literal encoding option says, warning about lost precision
+fn float_literal(value: f64, span: Span, state: &mut ParseState<'_>) -> ExprTree {
+    let single = value as f32;
+    let exact = f64::from(single) == value;
+    let expr: Expr<ExprTree> = match state.options.literals {
+        LiteralEncoding::Exact if exact => ExprBinaryFloat32Literal::from(single).into(),
+        LiteralEncoding::Lossy if single.is_finite() => {
+            if !exact {
+                state.warnings.push(
+                    Diagnostic::warning(Code::LossyLiteral, "float literal loses precision", span)
+                        .with_label(format!("written as {}", f64::from(single)))
+                        .with_help(format!(
+                            "the difference to the written value is {:e}",
+                            f64::from(single) - value
+                        )),
+                );
+            }
+            ExprBinaryFloat32Literal::from(single).into()
+        }
+        _ => ExprBinaryFloat64Literal::from(value).into(),
+    };
+    ExprTree::from(expr)
+}
+
+/// Applies the function of a superscript such as `²` to the operand before it, which binds tighter than anything else
+///
+/// Returns the start of the operand and the operand with the function applied.
+fn superscript(
+    symbols: &mut Vec<ParsedSymbol>,
+    function: Function,
+    span: Span,
+) -> Result<(usize, Symbol)> {
+    match symbols.pop() {
+        Some(ParsedSymbol {
+            symbol: Symbol::Operand(operand),
+            start,
+            ..
+        }) => Ok((start, Symbol::Operand(function.compose(vec![operand])))),
+        _ => Err(Diagnostic::error(
+            Code::ExpectedExpression,
+            "expected expression before superscript",
+            span,
+        )
+        .with_label("a superscript applies to the expression before it")
+        .into()),
+    }
+}
+
+/// Checks whether the next token opens an argument list
+fn is_call<I: Iterator<Item = Result<ParsedToken>>>(tokens: &mut Peekable<I>) -> bool {
+    matches!(
+        tokens.peek(),
+        Some(Ok(ParsedToken {
+            token: Token::LeftParenthesis,
+            ..
+        }))
+    )
+}
+
+/// Inserts an implicit multiplication between each two adjacent operands, unless implicit multiplication is off
+fn insert_implicit_multiplications(symbols: &mut Vec<ParsedSymbol>, state: &mut ParseState<'_>) {
+    if state.options.implicit_multiplication == ImplicitMultiplication::Off {
+        return;
+    }
+
+    let mut index = 1;
+    while index < symbols.len() {
+        let previous = &symbols[index - 1];
+        let current = &symbols[index];
+        let adjacent = match (&previous.symbol, &current.symbol) {
+            (Symbol::Operand(_), Symbol::Operand(_)) => true,
+            // Root signs are only prefix operators, so `2√x` is a product as well
+            (Symbol::Operand(_), Symbol::Operator(Operator::Radical(_))) => true,
+            _ => false,
+        };
+        if adjacent {
+            if state.options.implicit_multiplication == ImplicitMultiplication::Warn {
+                state.warnings.push(
+                    Diagnostic::warning(
+                        Code::ImplicitMultiplication,
+                        "implicit multiplication",
+                        Span::new(previous.start, current.end),
+                    )
+                    .with_label("these expressions are multiplied")
+                    .with_help("write `*` between the expressions to multiply them explicitly"),
+                );
+            }
+            let multiplication = ParsedSymbol {
+                symbol: Symbol::Operator(Operator::ImplicitMultiplication),
+                start: previous.end,
+                end: current.start,
+                literal: false,
+            };
+            symbols.insert(index, multiplication);
+            index += 1;
+        }
+        index += 1;
+    }
+}
+
+/// Marks all operators in place of an operand as unary operators and checks that operands and operators alternate.
+fn mark_unary_operators(symbols: &mut [ParsedSymbol]) -> Result<()> {
     // What is expected in a sequence without unary operators
     let mut expecting = Expecting::Operand;
+    let mut previous_end = 0;
 
-    let mut unary_operator_stack: Vec<ParsedOperator> = Vec::new();
-    for i in 0..symbols.len() {
-        let parsed_symbol = symbols[i].take().expect("Symbol vec not clean");
+    for parsed_symbol in symbols.iter_mut() {
         match parsed_symbol.symbol {
             Symbol::Operator(op) => match expecting {
                 Expecting::Operand => {
-                    unary_operator_stack.push(ParsedOperator {
-                        operator: op,
-                        start: parsed_symbol.start,
-                        end: parsed_symbol.end,
-                    });
+                    if op.unary_precedence().is_none() {
+                        return Err(illegal_unary_operator(op, parsed_symbol.span()).into());
+                    }
+                    parsed_symbol.symbol = Symbol::UnaryOperator(op);
                 }
                 Expecting::Operator => {
                     expecting = Expecting::Operand;
-                    symbols[i] = Some(parsed_symbol);
                 }
             },
+            Symbol::UnaryOperator(_) => {}
             Symbol::Operand(_) => match expecting {
                 Expecting::Operand => {
-                    let end: usize = parsed_symbol.end;
-                    let mut start: usize = parsed_symbol.start;
-                    let mut expr_to_wrap = match parsed_symbol.symbol {
-                        Symbol::Operand(expr) => expr,
-                        _ => unreachable!(),
-                    };
-
-                    for unary_operator in unary_operator_stack.iter().rev() {
-                        start = unary_operator.start;
-                        expr_to_wrap = wrap(expr_to_wrap, *unary_operator)?;
-                    }
                     expecting = Expecting::Operator;
-                    symbols[i] = Some(ParsedSymbol {
-                        end,
-                        start,
-                        symbol: Symbol::Operand(expr_to_wrap),
-                    });
+                    previous_end = parsed_symbol.end;
                 }
                 Expecting::Operator => {
-                    return Err(anyhow!(
-                        "Expected operator at {}-{} found expression",
-                        parsed_symbol.start,
-                        parsed_symbol.end
-                    ));
+                    return Err(Diagnostic::error(
+                        Code::ExpectedOperator,
+                        "expected operator, found expression",
+                        parsed_symbol.span(),
+                    )
+                    .with_label("expected an operator before this expression")
+                    .with_secondary(
+                        Span::new(previous_end, previous_end),
+                        "previous expression ends here",
+                    )
+                    .with_help(
+                        "write `*` between the expressions to multiply them, or use `--implicit-multiplication on`",
+                    )
+                    .into());
                 }
             },
         }
     }
 
-    Ok(to_cleaned_symbols(symbols))
+    if expecting == Expecting::Operand {
+        let last = symbols.last().expect("Group is not empty");
+        return Err(Diagnostic::error(
+            Code::ExpectedExpression,
+            "expected expression after operator",
+            last.span(),
+        )
+        .with_label("operator is missing its right operand")
+        .into());
+    }
+
+    Ok(())
 }
 
-/// Composes all binary expressions in the sequence of symbols
-fn compose_binary_expressions(
-    postfix_symbols: impl DoubleEndedIterator<Item = ParsedSymbol>,
+/// Composes all unary and binary expressions in the sequence of symbols
+fn compose_expressions(
+    postfix_symbols: Vec<ParsedSymbol>,
+    options: &ParseOptions,
 ) -> Result<ExprTree> {
     let mut stack: Vec<ExpressionInProgress> = Vec::new();
 
-    let mut found_expr: Option<ExprTree> = None;
+    let mut found_expr: Option<(ExprTree, Span)> = None;
+    let group_span = Span::new(
+        postfix_symbols
+            .iter()
+            .map(|symbol| symbol.start)
+            .min()
+            .unwrap_or(0),
+        postfix_symbols
+            .iter()
+            .map(|symbol| symbol.end)
+            .max()
+            .unwrap_or(0),
+    );
 
-    for parsed_symbol in postfix_symbols.rev() {
-        let add: Option<ExpressionInProgress> = match stack.last_mut() {
-            None =>
-            // No state in progress
-            {
-                match parsed_symbol.symbol {
-                    Symbol::Operator(o) => {
-                        stack.push(ExpressionInProgress::Operator {
-                            operator: ParsedOperator {
-                                operator: o,
-                                start: parsed_symbol.start,
-                                end: parsed_symbol.end,
-                            },
-                        });
-                        None
-                    }
-                    Symbol::Operand(expr) => {
-                        if found_expr.is_some() {
-                            return Err(anyhow!(
-                                "Found more than one expression in postfix expression"
-                            ));
-                        } else {
-                            found_expr = Some(expr);
-                            break;
-                        }
+    for parsed_symbol in postfix_symbols.into_iter().rev() {
+        let span = parsed_symbol.span();
+        let literal = parsed_symbol.literal;
+        let add: Option<ExpressionInProgress> = match parsed_symbol.symbol {
+            Symbol::Operator(o) => Some(ExpressionInProgress::Operator {
+                operator: ParsedOperator {
+                    operator: o,
+                    start: parsed_symbol.start,
+                    end: parsed_symbol.end,
+                },
+            }),
+            Symbol::UnaryOperator(o) => Some(ExpressionInProgress::UnaryOperator {
+                operator: ParsedOperator {
+                    operator: o,
+                    start: parsed_symbol.start,
+                    end: parsed_symbol.end,
+                },
+            }),
+            Symbol::Operand(expr) => match stack.last_mut() {
+                // No state in progress
+                None => {
+                    if let Some((_, found_span)) = found_expr {
+                        return Err(Diagnostic::error(
+                            Code::ExpectedOperator,
+                            "expected operator, found expression",
+                            span,
+                        )
+                        .with_label("expected an operator between the expressions")
+                        .with_secondary(found_span, "other expression")
+                        .into());
+                    } else {
+                        found_expr = Some((expr, span));
+                        break;
                     }
                 }
-            }
-            Some(in_progress) => match parsed_symbol.symbol {
-                Symbol::Operator(o) => Some(ExpressionInProgress::Operator {
-                    operator: ParsedOperator {
-                        operator: o,
-                        end: parsed_symbol.end,
-                        start: parsed_symbol.start,
-                    },
-                }),
-                Symbol::Operand(expr) => {
-                    in_progress.add_operand(expr);
+                Some(in_progress) => {
+                    in_progress.add_operand(expr, literal);
                     None
                 }
             },
@@ -231,94 +1199,87 @@ fn compose_binary_expressions(
         if let Some(add) = add {
             stack.push(add);
         }
-        while let Some(ExpressionInProgress::LHS {
-            operator: _,
-            lhs: _,
-            rhs: _,
-        }) = stack.last()
-        {
+        while stack.last().is_some_and(ExpressionInProgress::is_complete) {
             let in_progress = stack.pop().expect("Stack not empty");
-            let expr = in_progress.compose_into()?;
+            let expr = in_progress.compose_into(options)?;
             match stack.last_mut() {
                 None => return Ok(expr),
-                Some(in_progress) => in_progress.add_operand(expr),
+                Some(in_progress) => in_progress.add_operand(expr, false),
             }
         }
     }
 
-    if let Some(expr) = found_expr {
-        if stack.is_empty() {
-            return Ok(expr);
-        } else {
-            return Err(anyhow!(
-                    "Found expression before all operators were consumed after converting to postfix notation"
-                ));
+    if let Some((expr, span)) = found_expr {
+        match stack.last() {
+            None => return Ok(expr),
+            Some(in_progress) => {
+                return Err(Diagnostic::error(
+                    Code::ExpectedOperator,
+                    "expected operator, found expression",
+                    span,
+                )
+                .with_label("not an operand of any operator")
+                .with_secondary(in_progress.operator().span(), "operator missing an operand")
+                .into())
+            }
         }
     }
 
-    Err(anyhow!("Failed to compose expression, no complete expression found on composition stack. Composition stack: {:?}", stack))
+    let error = match stack.last() {
+        Some(in_progress) => Diagnostic::error(
+            Code::ExpectedExpression,
+            "expected expression after operator",
+            in_progress.operator().span(),
+        )
+        .with_label("operator is missing an operand"),
+        None => Diagnostic::error(Code::ExpectedExpression, "expected expression", group_span)
+            .with_label("no expression found here"),
+    };
+    Err(error.into())
 }
 
 /// Converts a sequence of symbols in infix notation to postfix notation
+///
+/// Binary operators are popped from the operator stack by operators of lower precedence, or of equal precedence
+/// if they are left associative. Unary operators are prefix operators, so they are only ever pushed on the stack.
 fn shunting_yard_algorithm(symbols: Vec<ParsedSymbol>) -> Result<Vec<ParsedSymbol>> {
     let mut output: Vec<ParsedSymbol> = Vec::new();
-    let mut operator_stack: Vec<ParsedOperator> = Vec::new();
+    let mut operator_stack: Vec<ParsedSymbol> = Vec::new();
 
     for parsed_symbol in symbols {
         match parsed_symbol.symbol {
             Symbol::Operand(_) => output.push(parsed_symbol),
+            Symbol::UnaryOperator(_) => operator_stack.push(parsed_symbol),
             Symbol::Operator(op) => {
-                let precedence = op.binary_precedence().ok_or(anyhow!(
-                    "Illegal use of operator \"{}\" as a binary operator at {}-{}",
-                    op,
-                    parsed_symbol.start,
-                    parsed_symbol.end
-                ))?;
-
-                while let Some(&top) = operator_stack.last() {
-                    if top
-                        .operator
-                        .binary_precedence()
-                        .expect("Illegal operator on shunting yard stack")
-                        >= precedence
+                let precedence = op
+                    .binary_precedence()
+                    .ok_or_else(|| illegal_binary_operator(op, parsed_symbol.span()))?;
+
+                while let Some(top) = operator_stack.last() {
+                    let top_precedence = top.symbol.stacked_precedence();
+                    if top_precedence > precedence
+                        || (top_precedence == precedence
+                            && op.associativity() == Associativity::Left)
                     {
-                        output.push(ParsedSymbol {
-                            start: top.start,
-                            end: top.end,
-                            symbol: Symbol::Operator(top.operator),
-                        });
-                        operator_stack.pop();
+                        output.push(operator_stack.pop().expect("Stack not empty"));
                     } else {
                         break;
                     }
                 }
-                operator_stack.push(ParsedOperator {
-                    operator: op,
-                    start: parsed_symbol.start,
-                    end: parsed_symbol.end,
-                });
+                operator_stack.push(parsed_symbol);
             }
         }
     }
-    for operator in operator_stack.iter().rev() {
-        output.push(ParsedSymbol {
-            start: operator.start,
-            end: operator.end,
-            symbol: Symbol::Operator(operator.operator),
-        });
-    }
+    output.extend(operator_stack.into_iter().rev());
 
     Ok(output)
 }
 
-/// Removes all `None` values from a vector of `Option`s
-fn to_cleaned_symbols<S>(symbols: &mut Vec<Option<S>>) -> () {
-    symbols.retain(|s| s.is_some());
-}
-
 fn compose_expression(operator: ParsedOperator, lhs: ExprTree, rhs: ExprTree) -> Result<ExprTree> {
     let e: Expr<ExprTree> = match operator.operator {
-        Operator::Asterisk => ExprMultiplication::from((lhs, rhs)).into(),
+        Operator::Asterisk | Operator::ImplicitMultiplication => {
+            ExprMultiplication::from((lhs, rhs)).into()
+        }
         Operator::Slash => ExprDivision::from((lhs, rhs)).into(),
         Operator::DoubleSlash => ExprIntDivision::from((lhs, rhs)).into(),
         Operator::Minus => ExprSubtraction::from((lhs, rhs)).into(),
@@ -326,46 +1287,123 @@ fn compose_expression(operator: ParsedOperator, lhs: ExprTree, rhs: ExprTree) ->
         Operator::Percent => ExprModulo::from((lhs, rhs)).into(),
         Operator::Caret => ExprPower::from((lhs, rhs)).into(),
         Operator::DoubleAsterisk => ExprPower::from((lhs, rhs)).into(),
-        _ => {
-            return Err(anyhow!(
-                "Illegal use of \"{}\" as binary operator at {}-{}",
-                operator.operator,
-                operator.start,
-                operator.end
-            ))
-        }
+        _ => return Err(illegal_binary_operator(operator.operator, operator.span()).into()),
     };
     Ok(e.into())
 }
 
-fn wrap(expr: ExprTree, unary_operator: ParsedOperator) -> Result<ExprTree> {
+fn illegal_unary_operator(operator: Operator, span: Span) -> Diagnostic {
+    Diagnostic::error(
+        Code::IllegalUnaryOperator,
+        format!("`{}` is not a unary operator", operator),
+        span,
+    )
+    .with_label("expected an expression or a unary operator")
+    .with_help("only `+`, `-` and root signs such as `√` can be used as unary operators")
+}
+
+fn illegal_binary_operator(operator: Operator, span: Span) -> Diagnostic {
+    Diagnostic::error(
+        Code::IllegalBinaryOperator,
+        format!("`{}` is not a supported binary operator", operator),
+        span,
+    )
+    .with_help("the supported binary operators are `+`, `-`, `*`, `/`, `//`, `%`, `^` and `**`")
+}
+
+/// Applies the unary operator to the expression
+///
+/// A minus right before a number literal or a constant makes a negative literal if negative literals are folded,
+/// so `-5` is a negative literal while `-(5)` is a negation.
+fn wrap(
+    expr: ExprTree,
+    unary_operator: ParsedOperator,
+    literal: bool,
+    options: &ParseOptions,
+) -> Result<ExprTree> {
     match unary_operator.operator {
         Operator::Plus => Ok(expr),
+        Operator::Minus if literal && options.fold_negative_literals => {
+            match negative_literal(&expr) {
+                Some(literal) => Ok(literal),
+                None => Ok(ExprTree::from(Expr::Negation(ExprNegation::from(expr)))),
+            }
+        }
         Operator::Minus => {
             let expr_obj = ExprNegation::from(expr);
             Ok(ExprTree::from(Expr::Negation(expr_obj)))
         }
-        _ => {
-            return Err(anyhow!(
-                "Illegal use of \"{}\" as unary operator at {}-{}",
-                unary_operator.operator,
-                unary_operator.start,
-                unary_operator.end
-            ))
+        Operator::Radical(degree) => Ok(radical(expr, degree)),
+        _ => Err(illegal_unary_operator(unary_operator.operator, unary_operator.span()).into()),
+    }
+}
+
+/// Root of the degree of a root sign, using the square and cube root expressions where possible
+fn radical(expr: ExprTree, degree: u8) -> ExprTree {
+    match degree {
+        2 => Function::SquareRoot.compose(vec![expr]),
+        3 => Function::CubeRoot.compose(vec![expr]),
+        degree => {
+            let degree: Expr<ExprTree> = ExprUnsignedIntLiteral::from(u64::from(degree)).into();
+            Function::Root.compose(vec![ExprTree::from(degree), expr])
         }
     }
 }
 
+/// Negative literal with the value of the negated expression, if the expression is a non-negative number literal
+///
+/// Negative literals are already negated, so `--5` stays a negation of `-5`.
+fn negative_literal(expr: &ExprTree) -> Option<ExprTree> {
+    let literal: Expr<ExprTree> = match expr.inner() {
+        Expr::UnsignedIntLiteral(literal) => {
+            let value: u64 = infallible(literal.clone().try_into());
+            ExprSignedIntLiteral::from(-i64::try_from(value).ok()?).into()
+        }
+        Expr::SignedIntLiteral(literal) => {
+            let value: i64 = infallible(literal.clone().try_into());
+            if value < 0 {
+                return None;
+            }
+            ExprSignedIntLiteral::from(-value).into()
+        }
+        Expr::BinaryFloat32Literal(literal) => {
+            let value: f32 = infallible(literal.clone().try_into());
+            if value.is_sign_negative() {
+                return None;
+            }
+            ExprBinaryFloat32Literal::from(-value).into()
+        }
+        Expr::BinaryFloat64Literal(literal) => {
+            let value: f64 = infallible(literal.clone().try_into());
+            if value.is_sign_negative() {
+                return None;
+            }
+            ExprBinaryFloat64Literal::from(-value).into()
+        }
+        _ => return None,
+    };
+    Some(ExprTree::from(literal))
+}
+
 #[derive(Debug)]
 enum ExpressionInProgress {
     Operator {
         operator: ParsedOperator,
     },
-    RHS {
+    UnaryOperator {
+        operator: ParsedOperator,
+    },
+    Unary {
+        operator: ParsedOperator,
+        operand: ExprTree,
+        /// Whether the operand is a number literal or a constant
+        literal: bool,
+    },
+    Rhs {
         operator: ParsedOperator,
         rhs: ExprTree,
     },
-    LHS {
+    Lhs {
         operator: ParsedOperator,
         lhs: ExprTree,
         rhs: ExprTree,
@@ -373,43 +1411,100 @@ enum ExpressionInProgress {
 }
 
 impl ExpressionInProgress {
-    /// Adds an operand to the expression in progress
+    /// Adds an operand to the expression in progress, which is a literal if it is a number literal or a constant
     ///
     /// # Panics
     /// Panics if the expression is already complete
-    pub(crate) fn add_operand(&mut self, operand: ExprTree) -> () {
+    pub(crate) fn add_operand(&mut self, operand: ExprTree, literal: bool) {
         *self = match self {
-            ExpressionInProgress::Operator { operator } => ExpressionInProgress::RHS {
+            ExpressionInProgress::Operator { operator } => ExpressionInProgress::Rhs {
                 operator: *operator,
                 rhs: operand,
             },
-            ExpressionInProgress::RHS { operator, rhs } => ExpressionInProgress::LHS {
+            ExpressionInProgress::Rhs { operator, rhs } => ExpressionInProgress::Lhs {
                 operator: *operator,
                 lhs: operand,
                 rhs: rhs.clone(),
             },
+            ExpressionInProgress::UnaryOperator { operator } => ExpressionInProgress::Unary {
+                operator: *operator,
+                operand,
+                literal,
+            },
             _ => panic!("Attempted to add operand to completed expression"),
         }
     }
 
+    fn operator(&self) -> ParsedOperator {
+        match self {
+            ExpressionInProgress::Operator { operator }
+            | ExpressionInProgress::UnaryOperator { operator }
+            | ExpressionInProgress::Unary { operator, .. }
+            | ExpressionInProgress::Rhs { operator, .. }
+            | ExpressionInProgress::Lhs { operator, .. } => *operator,
+        }
+    }
+
+    /// Checks whether the expression in progress has all its operands
+    pub(crate) fn is_complete(&self) -> bool {
+        matches!(
+            self,
+            ExpressionInProgress::Lhs { .. } | ExpressionInProgress::Unary { .. }
+        )
+    }
+
     /// Composes the expression in progress into an expression tree
     ///
     /// # Panics
     /// Panics if the expression is incomplete
-    pub(crate) fn compose_into(self) -> Result<ExprTree> {
+    pub(crate) fn compose_into(self, options: &ParseOptions) -> Result<ExprTree> {
         match self {
             ExpressionInProgress::Operator { operator: _ } => {
                 panic!("Attempted to compose incomplete expression")
             }
-            ExpressionInProgress::RHS {
+            ExpressionInProgress::Rhs {
                 operator: _,
                 rhs: _,
             } => {
                 panic!("Attempted to compose incomplete expression")
             }
-            ExpressionInProgress::LHS { operator, lhs, rhs } => {
+            ExpressionInProgress::UnaryOperator { operator: _ } => {
+                panic!("Attempted to compose incomplete expression")
+            }
+            ExpressionInProgress::Lhs { operator, lhs, rhs } => {
                 compose_expression(operator, lhs, rhs)
             }
+            ExpressionInProgress::Unary {
+                operator,
+                operand,
+                literal,
+            } => wrap(operand, operator, literal, options),
+        }
+    }
+}
+
+/// Kind of group of tokens being parsed, determines which tokens close it
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Group {
+    TopLevel,
+    Parenthesis,
+    Arguments,
+}
+
+/// The way a group of tokens was closed, with the index after the closing token
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Closing {
+    End { end: usize },
+    RightParenthesis { end: usize },
+    Comma { end: usize },
+}
+
+impl Closing {
+    fn end(&self) -> usize {
+        match self {
+            Closing::End { end } | Closing::RightParenthesis { end } | Closing::Comma { end } => {
+                *end
+            }
         }
     }
 }
@@ -427,17 +1522,57 @@ struct ParsedOperator {
     end: usize,
 }
 
+impl ParsedOperator {
+    fn span(&self) -> Span {
+        Span::new(self.start, self.end)
+    }
+}
+
 #[derive(Debug)]
 struct ParsedSymbol {
     symbol: Symbol,
     start: usize,
     end: usize,
+    /// Whether the symbol is a number literal or a constant, which a unary minus right before it folds into a
+    /// negative literal
+    literal: bool,
+}
+
+impl ParsedSymbol {
+    fn span(&self) -> Span {
+        Span::new(self.start, self.end)
+    }
 }
 
 #[derive(Debug)]
 pub(crate) enum Symbol {
     Operand(ExprTree),
     Operator(Operator),
+    UnaryOperator(Operator),
+}
+
+impl Symbol {
+    /// Precedence of an operator symbol placed on the shunting yard operator stack
+    ///
+    /// # Panics
+    /// Panics if the symbol is an operand or an operator illegal in its position
+    fn stacked_precedence(&self) -> usize {
+        match self {
+            Symbol::Operator(op) => op
+                .binary_precedence()
+                .expect("Illegal operator on shunting yard stack"),
+            Symbol::UnaryOperator(op) => op
+                .unary_precedence()
+                .expect("Illegal operator on shunting yard stack"),
+            Symbol::Operand(_) => panic!("Operand on shunting yard stack"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Associativity {
+    Left,
+    Right,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -459,6 +1594,10 @@ pub(crate) enum Operator {
     GreaterThan,
     GreaterThanOrEqual,
     Period,
+    /// Multiplication of adjacent expressions, which binds tighter than `*`
+    ImplicitMultiplication,
+    /// A root sign such as `√`, with the degree of the root
+    Radical(u8),
 }
 
 impl Display for Operator {
@@ -474,13 +1613,17 @@ impl Display for Operator {
             Operator::Percent => "%",
             Operator::Caret => "^",
             Operator::Comma => ",",
-            Operator::Equals => "==",
+            Operator::Equals => "=",
             Operator::NotEquals => "!=",
             Operator::LessThan => "<",
             Operator::LessThanOrEqual => "<=",
             Operator::GreaterThan => ">",
             Operator::GreaterThanOrEqual => ">=",
             Operator::Period => ".",
+            Operator::ImplicitMultiplication => "*",
+            Operator::Radical(3) => "∛",
+            Operator::Radical(4) => "∜",
+            Operator::Radical(_) => "√",
         };
         write!(f, "{}", s)
     }
@@ -493,9 +1636,403 @@ impl Operator {
             Operator::Asterisk | Operator::Slash | Operator::DoubleSlash | Operator::Percent => {
                 Some(2)
             }
-            Operator::DoubleAsterisk | Operator::Caret => Some(3),
+            Operator::ImplicitMultiplication => Some(3),
+            Operator::DoubleAsterisk | Operator::Caret => Some(5),
 
             _ => None,
         }
     }
+
+    /// Precedence of the operator used as a prefix unary operator
+    ///
+    /// Unary operators bind tighter than multiplication, but looser than exponentiation, so `-x^2` is `-(x^2)`.
+    pub(crate) fn unary_precedence(&self) -> Option<usize> {
+        match self {
+            Operator::Plus | Operator::Minus | Operator::Radical(_) => Some(4),
+
+            _ => None,
+        }
+    }
+
+    /// Associativity of the operator used as a binary operator
+    pub(crate) fn associativity(&self) -> Associativity {
+        match self {
+            Operator::DoubleAsterisk | Operator::Caret => Associativity::Right,
+
+            _ => Associativity::Left,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use fef::v0::expr::{
+        traits::{BinaryOperationExpr, UnaryOperationExpr},
+        Expr, ExprBinaryFloat32Literal, ExprBinaryFloat64Literal, ExprSignedIntLiteral, ExprTree,
+    };
+
+    use super::{
+        into_expr_tree, ImplicitMultiplication, LiteralEncoding, ParseOptions, VariableOrder,
+    };
+    use crate::{
+        diagnostic::{Code, Diagnostic},
+        parser::Tokens,
+    };
+
+    fn options() -> ParseOptions {
+        ParseOptions {
+            literals: LiteralEncoding::Exact,
+            fold_negative_literals: true,
+            implicit_multiplication: ImplicitMultiplication::Off,
+            builtin_constants: true,
+            variables: Vec::new(),
+            variable_order: VariableOrder::Appearance,
+            strict_variables: false,
+        }
+    }
+
+    /// The tree of the formula, the names of its variables and the warnings
+    fn read(
+        text: &str,
+        options: &ParseOptions,
+    ) -> Result<(ExprTree, Vec<String>, Vec<Diagnostic>)> {
+        let mut variables = Vec::new();
+        let mut warnings = Vec::new();
+        let tree = into_expr_tree(
+            &mut Tokens::new(text.chars()),
+            &mut variables,
+            options,
+            &mut warnings,
+        )?;
+        Ok((tree, variables, warnings))
+    }
+
+    fn parse_with(text: &str, options: &ParseOptions) -> Result<ExprTree> {
+        Ok(read(text, options)?.0)
+    }
+
+    fn parse(text: &str) -> ExprTree {
+        parse_with(text, &options())
+            .unwrap_or_else(|error| panic!("`{}` can't be parsed: {:?}", text, error))
+    }
+
+    /// Code of the error the formula can't be parsed with
+    fn error_code(text: &str, options: &ParseOptions) -> Code {
+        match parse_with(text, options) {
+            Ok(_) => panic!("`{}` is parsed", text),
+            Err(error) => {
+                error
+                    .downcast_ref::<Diagnostic>()
+                    .unwrap_or_else(|| panic!("`{}` fails without a diagnostic", text))
+                    .code
+            }
+        }
+    }
+
+    /// Codes of the warnings of the formula
+    fn warning_codes(text: &str, options: &ParseOptions) -> Vec<Code> {
+        let (_, _, warnings) = read(text, options)
+            .unwrap_or_else(|error| panic!("`{}` can't be parsed: {:?}", text, error));
+        warnings.iter().map(|warning| warning.code).collect()
+    }
+
+    #[test]
+    fn functions_are_specialised_expressions() {
+        assert!(matches!(parse("sqrt(x)").inner(), Expr::SquareRoot(_)));
+        assert!(matches!(parse("cbrt(x)").inner(), Expr::CubeRoot(_)));
+        assert!(matches!(parse("sq(x)").inner(), Expr::Square(_)));
+        assert!(matches!(parse("cube(x)").inner(), Expr::Cube(_)));
+        assert!(matches!(parse("recip(x)").inner(), Expr::Reciprocal(_)));
+        assert!(matches!(parse("introot(3, x)").inner(), Expr::IntRoot(_)));
+        match parse("root(3, x + 1)").inner() {
+            Expr::Root(root) => {
+                assert_eq!(*root.lhs(), parse("3"));
+                assert_eq!(*root.rhs(), parse("x + 1"));
+            }
+            expr => panic!("`root` is parsed as {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn functions_check_their_arity() {
+        for text in ["sqrt(x, y)", "sq()", "root(x)", "introot(1, 2, 3)"] {
+            assert_eq!(
+                error_code(text, &options()),
+                Code::WrongArgumentCount,
+                "{}",
+                text
+            );
+        }
+        assert_eq!(error_code("sine(x)", &options()), Code::UnknownFunction);
+    }
+
+    #[test]
+    fn exponentiation_is_right_associative() {
+        assert_eq!(parse("2^3^2"), parse("2^(3^2)"));
+        assert_eq!(parse("2**3**2"), parse("2**(3**2)"));
+        assert_eq!(parse("2^3**2"), parse("2^(3^2)"));
+        assert_ne!(parse("2^3^2"), parse("(2^3)^2"));
+    }
+
+    #[test]
+    fn exponentiation_binds_tighter_than_unary_minus() {
+        assert_eq!(parse("-x^2"), parse("-(x^2)"));
+        assert_eq!(parse("-2^2"), parse("-(2^2)"));
+        assert_eq!(parse("2^-x"), parse("2^(-x)"));
+        assert_eq!(parse("-x^2 * y"), parse("(-(x^2)) * y"));
+        assert!(matches!(parse("-x^2").inner(), Expr::Negation(_)));
+    }
+
+    #[test]
+    fn integers_get_the_smallest_exact_encoding() {
+        assert!(matches!(
+            parse("4294967295").inner(),
+            Expr::UnsignedIntLiteral(_)
+        ));
+        assert!(matches!(
+            parse("4294967296").inner(),
+            Expr::SignedIntLiteral(_)
+        ));
+        assert!(matches!(
+            parse("9223372036854775807").inner(),
+            Expr::SignedIntLiteral(_)
+        ));
+        assert!(matches!(
+            parse("9223372036854775808").inner(),
+            Expr::BinaryFloat64Literal(_)
+        ));
+        assert_eq!(
+            warning_codes("9223372036854775808", &options()),
+            [Code::LossyLiteral]
+        );
+    }
+
+    #[test]
+    fn floats_get_the_encoding_of_the_option() {
+        let exact = options();
+        assert!(matches!(
+            parse_with("0.5", &exact).unwrap().inner(),
+            Expr::BinaryFloat32Literal(_)
+        ));
+        assert!(matches!(
+            parse_with("0.1", &exact).unwrap().inner(),
+            Expr::BinaryFloat64Literal(_)
+        ));
+        assert_eq!(warning_codes("0.1", &exact), []);
+
+        let f64 = ParseOptions {
+            literals: LiteralEncoding::F64,
+            ..options()
+        };
+        assert!(matches!(
+            parse_with("0.5", &f64).unwrap().inner(),
+            Expr::BinaryFloat64Literal(_)
+        ));
+
+        let lossy = ParseOptions {
+            literals: LiteralEncoding::Lossy,
+            ..options()
+        };
+        assert!(matches!(
+            parse_with("0.1", &lossy).unwrap().inner(),
+            Expr::BinaryFloat32Literal(_)
+        ));
+        assert_eq!(warning_codes("0.1", &lossy), [Code::LossyLiteral]);
+        assert_eq!(warning_codes("0.5", &lossy), []);
+        // Floats out of the range of 32-bit floats stay 64-bit floats
+        assert!(matches!(
+            parse_with("1e300", &lossy).unwrap().inner(),
+            Expr::BinaryFloat64Literal(_)
+        ));
+    }
+
+    #[test]
+    fn negated_literals_are_folded() {
+        let minus_five: Expr<ExprTree> = ExprSignedIntLiteral::from(-5).into();
+        assert_eq!(parse("-5"), ExprTree::from(minus_five));
+        let minus_half: Expr<ExprTree> = ExprBinaryFloat32Literal::from(-0.5).into();
+        assert_eq!(parse("-0.5"), ExprTree::from(minus_half));
+        let minus_pi: Expr<ExprTree> = ExprBinaryFloat64Literal::from(-std::f64::consts::PI).into();
+        assert_eq!(parse("-pi"), ExprTree::from(minus_pi));
+        assert_eq!(parse("2 * -5"), parse("2 * (-5)"));
+        assert!(matches!(parse("2 - 5").inner(), Expr::Subtraction(_)));
+    }
+
+    #[test]
+    fn other_negations_are_not_folded() {
+        assert!(matches!(parse("-(5)").inner(), Expr::Negation(_)));
+        assert!(matches!(parse("-x").inner(), Expr::Negation(_)));
+        match parse("--5").inner() {
+            Expr::Negation(negation) => assert_eq!(*negation.inner(), parse("-5")),
+            expr => panic!("`--5` is parsed as {:?}", expr),
+        }
+
+        let unfolded = ParseOptions {
+            fold_negative_literals: false,
+            ..options()
+        };
+        assert!(matches!(
+            parse_with("-5", &unfolded).unwrap().inner(),
+            Expr::Negation(_)
+        ));
+    }
+
+    #[test]
+    fn adjacent_expressions_are_multiplied_when_enabled() {
+        let on = ParseOptions {
+            implicit_multiplication: ImplicitMultiplication::On,
+            ..options()
+        };
+        let implicit = |text: &str| parse_with(text, &on).unwrap();
+        assert_eq!(implicit("1/2x"), parse("1/(2*x)"));
+        assert_eq!(implicit("3(a+b)"), parse("3*(a+b)"));
+        assert_eq!(implicit("(a)(b)"), parse("a*b"));
+        assert_eq!(implicit("2x^2"), parse("2*x^2"));
+        assert_eq!(implicit("x(a+b)"), parse("x*(a+b)"));
+        assert_eq!(implicit("2sqrt(x)"), parse("2*sqrt(x)"));
+        assert_eq!(warning_codes("1/2x", &on), []);
+
+        let warn = ParseOptions {
+            implicit_multiplication: ImplicitMultiplication::Warn,
+            ..options()
+        };
+        assert_eq!(parse_with("3(a+b)", &warn).unwrap(), parse("3*(a+b)"));
+        assert_eq!(
+            warning_codes("(a)(b)2", &warn),
+            [Code::ImplicitMultiplication, Code::ImplicitMultiplication]
+        );
+    }
+
+    #[test]
+    fn adjacent_expressions_are_an_error_by_default() {
+        for text in ["1/2x", "3(a+b)", "(a)(b)"] {
+            assert_eq!(
+                error_code(text, &options()),
+                Code::ExpectedOperator,
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn number_followed_by_e_is_a_product_with_e() {
+        let on = ParseOptions {
+            implicit_multiplication: ImplicitMultiplication::On,
+            ..options()
+        };
+        assert_eq!(parse_with("2e", &on).unwrap(), parse("2*e"));
+        assert_eq!(parse_with("2e+x", &on).unwrap(), parse("2*e + x"));
+        assert_eq!(parse_with("2e-3", &on).unwrap(), parse("0.002"));
+        assert_eq!(error_code("1e", &options()), Code::ExpectedOperator);
+    }
+
+    #[test]
+    fn bindings_are_inlined() {
+        let (tree, variables, _) =
+            read("let q = (a + b) / 2\nlet r = q^2\nr + q * c", &options()).unwrap();
+        assert_eq!(tree, parse("((a + b) / 2)^2 + (a + b) / 2 * c"));
+        assert_eq!(variables, ["a", "b", "c"]);
+        assert_eq!(parse("let r = q^2; let q = a; r"), parse("a^2"));
+        assert_eq!(parse("let s = (a\n+ b); s"), parse("a + b"));
+    }
+
+    #[test]
+    fn cyclic_bindings_are_an_error() {
+        for text in [
+            "let a = a; a",
+            "let a = b + 1; let b = 2 * a; a",
+            "let u = u; 1",
+        ] {
+            assert_eq!(
+                error_code(text, &options()),
+                Code::CyclicBinding,
+                "{}",
+                text
+            );
+        }
+        assert_eq!(
+            error_code("let a = 1; let a = 2; a", &options()),
+            Code::DuplicateBinding
+        );
+    }
+
+    #[test]
+    fn unused_bindings_are_warned_about() {
+        let (tree, variables, warnings) = read("let u = x; let v = y; 1", &options()).unwrap();
+        assert_eq!(tree, parse("1"));
+        assert!(variables.is_empty());
+        let codes: Vec<Code> = warnings.iter().map(|warning| warning.code).collect();
+        assert_eq!(codes, [Code::UnusedBinding, Code::UnusedBinding]);
+        assert_eq!(warning_codes("let u = x; let v = u; v", &options()), []);
+    }
+
+    #[test]
+    fn variables_are_numbered_in_the_chosen_order() {
+        let (appearance, variables, _) = read("b * a", &options()).unwrap();
+        assert_eq!(variables, ["b", "a"]);
+
+        let declared = ParseOptions {
+            variables: vec!["a".to_string(), "b".to_string()],
+            ..options()
+        };
+        let (tree, variables, _) = read("b * a", &declared).unwrap();
+        assert_eq!(variables, ["a", "b"]);
+        assert_eq!(tree, parse("var a, b; b * a"));
+        assert_ne!(tree, appearance);
+
+        let alphabetical = ParseOptions {
+            variable_order: VariableOrder::Alphabetical,
+            ..options()
+        };
+        let (tree, variables, _) = read("c + a * b", &alphabetical).unwrap();
+        assert_eq!(variables, ["a", "b", "c"]);
+        assert_eq!(tree, parse("var a, b, c; c + a * b"));
+
+        let (_, variables, _) = read("var z; c + a", &alphabetical).unwrap();
+        assert_eq!(variables, ["z", "a", "c"]);
+    }
+
+    #[test]
+    fn strict_variables_must_be_declared() {
+        let strict = ParseOptions {
+            strict_variables: true,
+            ..options()
+        };
+        assert!(parse_with("var a, b; b * a", &strict).is_ok());
+        assert_eq!(
+            error_code("var a; b * a", &strict),
+            Code::UndeclaredVariable
+        );
+        assert_eq!(
+            error_code("var a, a; a", &options()),
+            Code::DuplicateDeclaration
+        );
+    }
+
+    #[test]
+    fn unicode_operators_are_their_ascii_forms() {
+        assert_eq!(parse("a × b ÷ c − d"), parse("a * b / c - d"));
+        assert_eq!(parse("√(a + b)"), parse("sqrt(a + b)"));
+        assert_eq!(parse("∛x"), parse("cbrt(x)"));
+        assert_eq!(parse("∜x"), parse("root(4, x)"));
+        assert_eq!(parse("(a + b)²"), parse("sq(a + b)"));
+        assert_eq!(parse("-x³"), parse("-cube(x)"));
+        assert_eq!(parse("√x²"), parse("sqrt(sq(x))"));
+        assert_eq!(parse("2·π"), parse("2 * pi"));
+
+        let without_constants = ParseOptions {
+            builtin_constants: false,
+            ..options()
+        };
+        assert_eq!(parse_with("π", &without_constants).unwrap(), parse("pi"));
+    }
+
+    #[test]
+    fn quoted_identifiers_are_always_variables() {
+        let (tree, variables, _) = read(r#""pi" * pi + "flow rate" + `let`"#, &options()).unwrap();
+        assert_eq!(variables, ["pi", "flow rate", "let"]);
+        assert_eq!(tree, parse("a * pi + b + c"));
+    }
 }
@@ -1,16 +1,26 @@
 use anyhow::Result;
 use arguments::Arguments;
 use clap::Parser;
-use tasks::{evaluate_from_file, write_to_file_from_stdin};
+use tasks::evaluate_from_file;
 
 mod arguments;
 mod cl_tools;
 mod cli;
+mod codegen;
+mod compose_tree;
+mod constants;
+mod decompile;
+mod diagnostic;
 mod evaluate_fef_stream;
+mod export;
+mod functions;
+mod inspect;
 mod into_expr_tree;
 mod parser;
+mod read_fef;
 mod tasks;
 mod write_as_fef;
+mod written;
 
 fn main() -> Result<()> {
     let arguments = Arguments::parse();
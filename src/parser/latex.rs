@@ -0,0 +1,796 @@
+use anyhow::Result;
+use phf::{phf_map, phf_set, Map, Set};
+
+use super::{
+    token::Token,
+    tokenizer::{unexpected_character, ParsedToken},
+};
+use crate::{
+    constants::Constant,
+    diagnostic::{Code, Diagnostic, Span},
+};
+
+/// Greek letter commands, read as variables named by the letters
+pub(crate) const GREEK_LETTER_MAP: Map<&'static str, char> = phf_map!(
+    "alpha" => 'α',
+    "beta" => 'β',
+    "gamma" => 'γ',
+    "delta" => 'δ',
+    "epsilon" => 'ϵ',
+    "varepsilon" => 'ε',
+    "zeta" => 'ζ',
+    "eta" => 'η',
+    "theta" => 'θ',
+    "vartheta" => 'ϑ',
+    "iota" => 'ι',
+    "kappa" => 'κ',
+    "lambda" => 'λ',
+    "mu" => 'μ',
+    "nu" => 'ν',
+    "xi" => 'ξ',
+    "varpi" => 'ϖ',
+    "rho" => 'ρ',
+    "varrho" => 'ϱ',
+    "sigma" => 'σ',
+    "varsigma" => 'ς',
+    "tau" => 'τ',
+    "upsilon" => 'υ',
+    "phi" => 'ϕ',
+    "varphi" => 'φ',
+    "chi" => 'χ',
+    "psi" => 'ψ',
+    "omega" => 'ω',
+    "Gamma" => 'Γ',
+    "Delta" => 'Δ',
+    "Theta" => 'Θ',
+    "Lambda" => 'Λ',
+    "Xi" => 'Ξ',
+    "Pi" => 'Π',
+    "Sigma" => 'Σ',
+    "Upsilon" => 'Υ',
+    "Phi" => 'Φ',
+    "Psi" => 'Ψ',
+    "Omega" => 'Ω',
+);
+
+/// Commands read as the tokens of operators
+const OPERATOR_COMMAND_MAP: Map<&'static str, Token> = phf_map!(
+    "cdot" => Token::Asterisk,
+    "times" => Token::Asterisk,
+    "ast" => Token::Asterisk,
+    "div" => Token::Slash,
+    "bmod" => Token::Percent,
+    "mod" => Token::Percent,
+);
+
+/// Commands that only change spacing or style, which are skipped
+const SPACING_COMMANDS: Set<&'static str> = phf_set!(
+    ",",
+    ";",
+    ":",
+    "!",
+    " ",
+    "quad",
+    "qquad",
+    "displaystyle",
+    "textstyle"
+);
+
+/// Commands whose braced argument is the name of a variable, such as `\mathrm{rate}`
+const NAME_COMMANDS: Set<&'static str> =
+    phf_set!("mathrm", "mathit", "text", "textrm", "operatorname");
+
+/// The command whose braced argument is always the name of a variable, even if a constant has the name
+const VARIABLE_COMMAND: &str = "mathit";
+
+/// Letter commands of the constants written upright, such as `\mathrm{\tau}`
+const CONSTANT_COMMAND_MAP: Map<&'static str, Constant> = phf_map!(
+    "pi" => Constant::Pi,
+    "tau" => Constant::Tau,
+    "varphi" => Constant::Phi,
+);
+
+/// Tokens of a formula written in LaTeX maths notation, with spans in the LaTeX source
+///
+/// Commands are read as the tokens of the expression language they stand for, e.g. `\frac{a}{b}` as
+/// `((a)/(b))` and `\sqrt[n]{x}` as `root(n, (x))`. Braces group like parentheses. Letters are variables
+/// on their own, so `ab` is two variables, and subscripts are part of the name, so `x_{1}` is the variable
+/// `x_1`. The tokens read before an error are returned before the error.
+///
+/// Numbers LaTeX splits apart, such as `2.5e3`, which is `2.5 \cdot e \cdot 3`, or `x^23`, which is
+/// `x^2 \cdot 3`, are reported as warnings.
+pub(crate) struct LatexTokens {
+    tokens: std::vec::IntoIter<ParsedToken>,
+    error: Option<anyhow::Error>,
+}
+
+impl LatexTokens {
+    pub(crate) fn new(source: &str, warnings: &mut Vec<Diagnostic>) -> Self {
+        let mut reader = LatexReader {
+            chars: source.chars().collect(),
+            read: 0,
+            tokens: Vec::new(),
+            warnings: Vec::new(),
+        };
+        let error = reader.read_group(GroupEnd::End).err();
+        warnings.append(&mut reader.warnings);
+        Self {
+            tokens: reader.tokens.into_iter(),
+            error,
+        }
+    }
+}
+
+impl Iterator for LatexTokens {
+    type Item = Result<ParsedToken>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.tokens.next() {
+            Some(token) => Some(Ok(token)),
+            None => self.error.take().map(Err),
+        }
+    }
+}
+
+/// What ends a group of LaTeX source
+#[derive(Debug, Clone, Copy)]
+enum GroupEnd {
+    /// The end of the source
+    End,
+    /// The `}` closing the `{` at the span
+    Brace(Span),
+    /// The `]` closing the optional argument of `\sqrt` opened at the span
+    Bracket(Span),
+}
+
+struct LatexReader {
+    chars: Vec<char>,
+    /// Number of chars already read (= index of the next char to read)
+    read: usize,
+    tokens: Vec<ParsedToken>,
+    warnings: Vec<Diagnostic>,
+}
+
+impl LatexReader {
+    fn peek_char(&self) -> Option<char> {
+        self.chars.get(self.read).copied()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.peek_char();
+        if c.is_some() {
+            self.read += 1;
+        }
+        c
+    }
+
+    fn push(&mut self, token: Token, span: Span) {
+        self.tokens.push(ParsedToken {
+            token,
+            start: span.start,
+            end: span.end,
+        });
+    }
+
+    /// The name of the command starting at `start`, which is letters or a single other character, and its end
+    fn command_at(&self, start: usize) -> (String, usize) {
+        let letters: String = self.chars[start + 1..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .collect();
+        if !letters.is_empty() {
+            let end = start + 1 + letters.chars().count();
+            return (letters, end);
+        }
+        match self.chars.get(start + 1) {
+            Some(c) => (c.to_string(), start + 2),
+            None => (String::new(), start + 1),
+        }
+    }
+
+    /// Skips whitespace, comments, `$` math delimiters and spacing commands
+    fn skip_blank(&mut self) {
+        loop {
+            match self.peek_char() {
+                Some(c) if c.is_whitespace() || c == '$' => self.read += 1,
+                Some('%') => while !matches!(self.next_char(), None | Some('\n')) {},
+                Some('\\') => {
+                    let (name, end) = self.command_at(self.read);
+                    if !SPACING_COMMANDS.contains(name.as_str()) {
+                        return;
+                    }
+                    self.read = end;
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Reads tokens up to the end of the group, returning the end of the closing character
+    fn read_group(&mut self, group_end: GroupEnd) -> Result<usize> {
+        // Square brackets opened in this group, which group like parentheses
+        let mut brackets: usize = 0;
+        loop {
+            self.skip_blank();
+            let start = self.read;
+            let Some(c) = self.next_char() else {
+                return match group_end {
+                    GroupEnd::End => Ok(start),
+                    GroupEnd::Brace(open) => Err(unclosed_group(open, start, '}')),
+                    GroupEnd::Bracket(open) => Err(unclosed_group(open, start, ']')),
+                };
+            };
+            let span = Span::new(start, self.read);
+            match c {
+                '}' => match group_end {
+                    GroupEnd::Brace(_) => return Ok(self.read),
+                    _ => {
+                        return Err(Diagnostic::error(
+                            Code::UnexpectedClosingParenthesis,
+                            "unexpected `}`",
+                            span,
+                        )
+                        .with_label("no matching `{`")
+                        .into())
+                    }
+                },
+                ']' if brackets == 0 && matches!(group_end, GroupEnd::Bracket(_)) => {
+                    return Ok(self.read)
+                }
+                ']' => {
+                    brackets = brackets.saturating_sub(1);
+                    self.push(Token::RightParenthesis, span);
+                }
+                '[' => {
+                    brackets += 1;
+                    self.push(Token::LeftParenthesis, span);
+                }
+                '{' => self.read_braced(span)?,
+                '^' => {
+                    self.push(Token::Caret, span);
+                    self.read_argument("^", span)?;
+                }
+                '_' => {
+                    return Err(Diagnostic::error(
+                        Code::UnexpectedCharacter,
+                        "subscript without a name",
+                        span,
+                    )
+                    .with_label("subscripts can only follow the name of a variable")
+                    .into())
+                }
+                '+' => self.push(Token::Plus, span),
+                '-' => self.push(Token::Minus, span),
+                '*' => self.push(Token::Asterisk, span),
+                '/' => self.push(Token::Slash, span),
+                '(' => self.push(Token::LeftParenthesis, span),
+                ')' => self.push(Token::RightParenthesis, span),
+                ',' => self.push(Token::Comma, span),
+                '=' => self.push(Token::Equals, span),
+                c if c.is_ascii_digit() || c == '.' => self.read_number(start)?,
+                c if c.is_alphabetic() => self.read_name(c.to_string(), start, false)?,
+                '\\' => self.read_command(start)?,
+                c => return Err(unexpected_character(c, start).into()),
+            }
+        }
+    }
+
+    /// Reads a group in braces as a parenthesised group, after the `{` at the span was read
+    fn read_braced(&mut self, open: Span) -> Result<()> {
+        self.push(Token::LeftParenthesis, open);
+        let end = self.read_group(GroupEnd::Brace(open))?;
+        self.push(Token::RightParenthesis, Span::new(end - 1, end));
+        Ok(())
+    }
+
+    /// Reads the argument of a command as a parenthesised group, either in braces or a single character
+    fn read_argument(&mut self, command: &str, command_span: Span) -> Result<()> {
+        self.skip_blank();
+        let start = self.read;
+        let missing_argument = |found: Span| {
+            Diagnostic::error(
+                Code::MissingArgument,
+                format!("expected argument of `{}`", command),
+                found,
+            )
+            .with_label("expected `{` or a single character")
+            .with_secondary(command_span, "argument of this")
+        };
+        let token = match self.peek_char() {
+            Some('{') => {
+                self.read += 1;
+                return self.read_braced(Span::new(start, self.read));
+            }
+            Some(c) if c.is_ascii_digit() => {
+                self.read += 1;
+                if command == "^" {
+                    self.warn_split_exponent(start);
+                }
+                Token::IntLiteral(u64::from(c.to_digit(10).expect("ASCII digit")))
+            }
+            Some(c) if c.is_alphabetic() => {
+                self.read += 1;
+                Token::Identifier(c.to_string())
+            }
+            Some('\\') => {
+                let (name, end) = self.command_at(start);
+                match letter_command(&name) {
+                    Some(name) => {
+                        self.read = end;
+                        Token::Identifier(name)
+                    }
+                    None => {
+                        return Err(missing_argument(Span::new(start, end))
+                            .with_help("enclose the argument in braces")
+                            .into())
+                    }
+                }
+            }
+            Some(_) => return Err(missing_argument(Span::new(start, start + 1)).into()),
+            None => return Err(missing_argument(Span::new(start, start)).into()),
+        };
+        let span = Span::new(start, self.read);
+        self.push(Token::LeftParenthesis, span);
+        self.push(token, span);
+        self.push(Token::RightParenthesis, span);
+        Ok(())
+    }
+
+    /// Reads a decimal integer or float literal starting at `start`, whose first character was read
+    fn read_number(&mut self, start: usize) -> Result<()> {
+        while matches!(self.peek_char(), Some(c) if c.is_ascii_digit() || c == '.') {
+            self.read += 1;
+        }
+        let text: String = self.chars[start..self.read].iter().collect();
+        let span = Span::new(start, self.read);
+        let token = if text == "." {
+            Token::Period
+        } else if text.contains('.') {
+            Token::FloatLiteral(text.parse().map_err(|err: std::num::ParseFloatError| {
+                Diagnostic::error(Code::InvalidNumber, "invalid float literal", span)
+                    .with_label(err.to_string())
+            })?)
+        } else {
+            Token::IntLiteral(text.parse().map_err(|err: std::num::ParseIntError| {
+                Diagnostic::error(Code::InvalidNumber, "invalid integer literal", span)
+                    .with_label(err.to_string())
+            })?)
+        };
+        self.push(token, span);
+        self.warn_split_number(start);
+        Ok(())
+    }
+
+    /// The end of the run of digits starting at `start`
+    fn digits_end(&self, start: usize) -> usize {
+        start
+            + self.chars[start..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .count()
+    }
+
+    /// Warns if the number starting at `start`, which was read, is followed by a letter and digits, such
+    /// as `2.5e3`, which is a product and not a number in scientific notation
+    fn warn_split_number(&mut self, start: usize) {
+        let Some(letter) = self.peek_char().filter(|c| c.is_ascii_alphabetic()) else {
+            return;
+        };
+        let mut digits_start = self.read + 1;
+        let sign = self.chars.get(digits_start).copied();
+        let exponent = matches!(letter, 'e' | 'E');
+        if exponent && matches!(sign, Some('+' | '-')) {
+            digits_start += 1;
+        }
+        let end = self.digits_end(digits_start);
+        if end == digits_start {
+            return;
+        }
+        let text: String = self.chars[start..end].iter().collect();
+        let mantissa: String = self.chars[start..self.read].iter().collect();
+        let warning = Diagnostic::warning(
+            Code::SplitNumber,
+            format!("`{}` is not read as one number", text),
+            Span::new(start, end),
+        )
+        .with_label(format!("the letter `{}` is a separate factor", letter));
+        let warning = match exponent {
+            true => {
+                let power: String = self.chars[self.read + 1..end]
+                    .iter()
+                    .filter(|c| **c != '+')
+                    .collect();
+                warning.with_help(format!(
+                    "write `{} \\times 10^{{{}}}` for scientific notation",
+                    mantissa, power
+                ))
+            }
+            false => warning.with_help("write `\\cdot` between the factors of a product"),
+        };
+        self.warnings.push(warning);
+    }
+
+    /// Warns if the digit starting at `start`, which was read as the exponent after `^`, is followed by
+    /// more digits, such as in `x^23`, where only `2` is the exponent
+    fn warn_split_exponent(&mut self, start: usize) {
+        let end = self.digits_end(self.read);
+        if end == self.read {
+            return;
+        }
+        let digits: String = self.chars[start..end].iter().collect();
+        self.warnings.push(
+            Diagnostic::warning(
+                Code::SplitNumber,
+                format!("only the first digit of `{}` is the exponent", digits),
+                Span::new(start, end),
+            )
+            .with_label(format!("the exponent is `{}`", self.chars[start]))
+            .with_help(format!(
+                "enclose an exponent of more digits in braces, e.g. `^{{{}}}`",
+                digits
+            )),
+        );
+    }
+
+    /// Reads the subscript following a name, if there is one, and the name as an identifier, which is
+    /// quoted if it is always a variable
+    fn read_name(&mut self, mut name: String, start: usize, quoted: bool) -> Result<()> {
+        self.skip_blank();
+        if self.peek_char() == Some('_') {
+            let underscore = self.read;
+            self.read += 1;
+            self.skip_blank();
+            let subscript = self.read_subscript(Span::new(underscore, underscore + 1))?;
+            name.push('_');
+            name.push_str(&subscript);
+        }
+        let token = match quoted {
+            true => Token::QuotedIdentifier(name),
+            false => Token::Identifier(name),
+        };
+        self.push(token, Span::new(start, self.read));
+        Ok(())
+    }
+
+    /// Reads a subscript of letters and digits, either in braces or a single character
+    fn read_subscript(&mut self, underscore: Span) -> Result<String> {
+        let start = self.read;
+        let invalid_subscript = |found: Span| {
+            Diagnostic::error(Code::MissingArgument, "expected subscript", found)
+                .with_label("expected letters or digits")
+                .with_secondary(underscore, "subscript of this")
+        };
+        match self.peek_char() {
+            Some('{') => {
+                self.read += 1;
+                let mut subscript = String::new();
+                loop {
+                    self.skip_blank();
+                    let position = self.read;
+                    match self.next_char() {
+                        Some('}') if !subscript.is_empty() => return Ok(subscript),
+                        Some(c) if c.is_alphanumeric() => subscript.push(c),
+                        Some('\\') => {
+                            let (name, end) = self.command_at(position);
+                            match letter_command(&name) {
+                                Some(letter) => {
+                                    self.read = end;
+                                    subscript.push_str(&letter);
+                                }
+                                None => {
+                                    return Err(invalid_subscript(Span::new(position, end)).into())
+                                }
+                            }
+                        }
+                        Some(_) => {
+                            return Err(invalid_subscript(Span::new(position, self.read)).into())
+                        }
+                        None => {
+                            return Err(unclosed_group(Span::new(start, start + 1), position, '}'))
+                        }
+                    }
+                }
+            }
+            Some(c) if c.is_alphanumeric() => {
+                self.read += 1;
+                Ok(c.to_string())
+            }
+            Some('\\') => {
+                let (name, end) = self.command_at(start);
+                let letter = letter_command(&name)
+                    .ok_or_else(|| invalid_subscript(Span::new(start, end)))?;
+                self.read = end;
+                Ok(letter)
+            }
+            Some(_) => Err(invalid_subscript(Span::new(start, start + 1)).into()),
+            None => Err(invalid_subscript(Span::new(start, start)).into()),
+        }
+    }
+
+    /// Reads a command starting with the backslash at `start`
+    fn read_command(&mut self, start: usize) -> Result<()> {
+        let (name, end) = self.command_at(start);
+        self.read = end;
+        let span = Span::new(start, end);
+
+        if let Some(token) = OPERATOR_COMMAND_MAP.get(name.as_str()) {
+            self.push(token.clone(), span);
+            return Ok(());
+        }
+        if let Some(letter) = letter_command(&name) {
+            return self.read_name(letter, start, false);
+        }
+        if name == "mathrm" {
+            if let Some(constant) = self.read_upright_constant() {
+                self.push(
+                    Token::Identifier(constant.name().to_string()),
+                    Span::new(start, self.read),
+                );
+                return Ok(());
+            }
+        }
+        if NAME_COMMANDS.contains(name.as_str()) {
+            let quoted = name == VARIABLE_COMMAND;
+            let name = self.read_braced_name(&name, span)?;
+            return self.read_name(name, start, quoted);
+        }
+
+        match name.as_str() {
+            "frac" | "dfrac" | "tfrac" => {
+                self.push(Token::LeftParenthesis, span);
+                self.read_argument(&format!("\\{}", name), span)?;
+                self.push(Token::Slash, span);
+                self.read_argument(&format!("\\{}", name), span)?;
+                self.push(Token::RightParenthesis, Span::new(self.read - 1, self.read));
+            }
+            "sqrt" => {
+                self.skip_blank();
+                if self.peek_char() == Some('[') {
+                    let open = Span::new(self.read, self.read + 1);
+                    self.read += 1;
+                    self.push(Token::Identifier("root".to_string()), span);
+                    self.push(Token::LeftParenthesis, open);
+                    let close = self.read_group(GroupEnd::Bracket(open))?;
+                    self.push(Token::Comma, Span::new(close - 1, close));
+                    self.read_argument("\\sqrt", span)?;
+                    self.push(Token::RightParenthesis, Span::new(self.read - 1, self.read));
+                } else {
+                    self.push(Token::Identifier("sqrt".to_string()), span);
+                    self.read_argument("\\sqrt", span)?;
+                }
+            }
+            "left" | "right" => {
+                self.skip_blank();
+                let delimiter = self.next_char();
+                let span = Span::new(start, self.read);
+                let token = match (name.as_str(), delimiter) {
+                    ("left", Some('(' | '[')) => Token::LeftParenthesis,
+                    ("right", Some(')' | ']')) => Token::RightParenthesis,
+                    _ => {
+                        return Err(Diagnostic::error(
+                            Code::UnexpectedCharacter,
+                            format!("unsupported delimiter after `\\{}`", name),
+                            span,
+                        )
+                        .with_label("expected a parenthesis or a square bracket")
+                        .into())
+                    }
+                };
+                self.push(token, span);
+            }
+            _ => {
+                return Err(Diagnostic::error(
+                    Code::UnknownCommand,
+                    format!("unknown command `\\{}`", name),
+                    span,
+                )
+                .with_label("not supported in formulas")
+                .with_help(
+                    "the supported commands are \\frac, \\dfrac, \\tfrac, \\sqrt, \\cdot, \\times, \\ast, \\div, \\bmod, \\mod, \\left, \\right, \\mathrm, \\mathit, \\text, \\textrm, \\operatorname and Greek letters",
+                )
+                .into())
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the braced letter command of a constant after `\mathrm`, such as `{\tau}`, if it follows
+    fn read_upright_constant(&mut self) -> Option<Constant> {
+        let start = self.read;
+        let constant = self.upright_constant();
+        if constant.is_none() {
+            self.read = start;
+        }
+        constant
+    }
+
+    fn upright_constant(&mut self) -> Option<Constant> {
+        self.skip_blank();
+        if self.next_char() != Some('{') {
+            return None;
+        }
+        self.skip_blank();
+        if self.peek_char() != Some('\\') {
+            return None;
+        }
+        let (name, end) = self.command_at(self.read);
+        let constant = CONSTANT_COMMAND_MAP.get(name.as_str()).copied()?;
+        self.read = end;
+        self.skip_blank();
+        (self.next_char() == Some('}')).then_some(constant)
+    }
+
+    /// Reads the braced argument of a command such as `\mathrm`, which is the name of a variable
+    fn read_braced_name(&mut self, command: &str, command_span: Span) -> Result<String> {
+        self.skip_blank();
+        let start = self.read;
+        let expected_name = |found: Span| {
+            Diagnostic::error(
+                Code::MissingArgument,
+                format!("expected name in braces after `\\{}`", command),
+                found,
+            )
+            .with_label("expected a name")
+            .with_secondary(command_span, "name of this")
+        };
+        if self.next_char() != Some('{') {
+            return Err(expected_name(Span::new(start, self.read)).into());
+        }
+        let mut name = String::new();
+        loop {
+            let position = self.read;
+            match self.next_char() {
+                Some('}') => break,
+                Some('\\' | '{') => {
+                    return Err(expected_name(Span::new(position, self.read)).into())
+                }
+                Some(c) => name.push(c),
+                None => return Err(unclosed_group(Span::new(start, start + 1), position, '}')),
+            }
+        }
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(expected_name(Span::new(start, self.read)).into());
+        }
+        Ok(name.to_string())
+    }
+}
+
+/// The name of the variable a letter command such as `\alpha` stands for, `\pi` being the constant `pi`
+fn letter_command(name: &str) -> Option<String> {
+    if name == "pi" {
+        return Some(name.to_string());
+    }
+    GREEK_LETTER_MAP.get(name).map(|letter| letter.to_string())
+}
+
+fn unclosed_group(open: Span, end: usize, closing: char) -> anyhow::Error {
+    Diagnostic::error(Code::UnclosedParenthesis, "unclosed group", open)
+        .with_label("group opened here")
+        .with_secondary(Span::new(end, end), format!("expected `{}` here", closing))
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use fef::v0::expr::ExprTree;
+
+    use super::LatexTokens;
+    use crate::{
+        diagnostic::{Code, Diagnostic},
+        into_expr_tree::{
+            into_expr_tree, ImplicitMultiplication, LiteralEncoding, ParseOptions, VariableOrder,
+        },
+        parser::{Token, Tokens},
+    };
+
+    fn options() -> ParseOptions {
+        ParseOptions {
+            literals: LiteralEncoding::Exact,
+            fold_negative_literals: true,
+            implicit_multiplication: ImplicitMultiplication::On,
+            builtin_constants: true,
+            variables: Vec::new(),
+            variable_order: VariableOrder::Appearance,
+            strict_variables: false,
+        }
+    }
+
+    /// The tree of the LaTeX formula and the codes of its warnings
+    fn read(source: &str) -> (ExprTree, Vec<Code>) {
+        let mut warnings = Vec::new();
+        let mut tokens = LatexTokens::new(source, &mut warnings);
+        let tree = into_expr_tree(&mut tokens, &mut Vec::new(), &options(), &mut warnings)
+            .unwrap_or_else(|error| panic!("`{}` can't be read: {:?}", source, error));
+        (tree, warnings.iter().map(|warning| warning.code).collect())
+    }
+
+    fn latex(source: &str) -> ExprTree {
+        read(source).0
+    }
+
+    /// The tree of a formula in the expression language
+    fn expr(text: &str) -> ExprTree {
+        into_expr_tree(
+            &mut Tokens::new(text.chars()),
+            &mut Vec::new(),
+            &options(),
+            &mut Vec::new(),
+        )
+        .unwrap_or_else(|error| panic!("`{}` can't be parsed: {:?}", text, error))
+    }
+
+    fn names(source: &str) -> Vec<String> {
+        LatexTokens::new(source, &mut Vec::new())
+            .map(|token| match token.expect("Token is read").token {
+                Token::Identifier(name) | Token::QuotedIdentifier(name) => name,
+                token => panic!("`{}` has the token {:?}", source, token),
+            })
+            .collect()
+    }
+
+    fn error_code(source: &str) -> Code {
+        let error = LatexTokens::new(source, &mut Vec::new())
+            .find_map(Result::err)
+            .unwrap_or_else(|| panic!("`{}` is read", source));
+        error
+            .downcast_ref::<Diagnostic>()
+            .unwrap_or_else(|| panic!("`{}` fails without a diagnostic", source))
+            .code
+    }
+
+    #[test]
+    fn fractions_are_divisions() {
+        assert_eq!(latex("\\frac{a + b}{2}"), expr("(a + b) / 2"));
+        assert_eq!(latex("\\frac12"), expr("1 / 2"));
+        assert_eq!(latex("\\dfrac{1}{x} \\cdot y"), expr("1 / x * y"));
+        assert_eq!(latex("\\tfrac{\\frac{a}{b}}{c}"), expr("(a / b) / c"));
+        assert_eq!(latex("\\frac{1}{2} m v^2"), expr("1 / 2 * m * v^2"));
+    }
+
+    #[test]
+    fn roots_have_optional_degrees() {
+        assert_eq!(latex("\\sqrt{x + 1}"), expr("sqrt(x + 1)"));
+        assert_eq!(latex("\\sqrt x"), expr("sqrt(x)"));
+        assert_eq!(latex("\\sqrt[3]{x + 1}"), expr("root(3, x + 1)"));
+        assert_eq!(latex("\\sqrt[n + 1]{x}"), expr("root(n + 1, x)"));
+        assert_eq!(latex("\\sqrt[3]{[a]}"), expr("root(3, (a))"));
+    }
+
+    #[test]
+    fn subscripts_are_part_of_names() {
+        assert_eq!(names("x_{1}"), ["x_1"]);
+        assert_eq!(names("x_1"), ["x_1"]);
+        assert_eq!(names("v_{max}"), ["v_max"]);
+        assert_eq!(names("\\alpha_{\\beta}"), ["α_β"]);
+        assert_eq!(names("\\mathrm{rate}_0"), ["rate_0"]);
+        assert_eq!(latex("x_12"), expr("x_1 * 2"));
+        assert_eq!(latex("x_{1} + x_1"), expr("x_1 + x_1"));
+        assert_eq!(error_code("x_{}"), Code::MissingArgument);
+        assert_eq!(error_code("_1"), Code::UnexpectedCharacter);
+    }
+
+    #[test]
+    fn letters_and_constants_are_multiplied() {
+        assert_eq!(latex("2\\pi r"), expr("2 * pi * r"));
+        assert_eq!(latex("ab"), expr("a * b"));
+        assert_eq!(latex("\\mathrm{\\tau} \\mathit{e}"), expr("tau * \"e\""));
+        assert_eq!(error_code("\\sin x"), Code::UnknownCommand);
+    }
+
+    #[test]
+    fn split_numbers_are_warned_about() {
+        let (tree, warnings) = read("2.5e3");
+        assert_eq!(tree, expr("2.5 * e * 3"));
+        assert_eq!(warnings, [Code::SplitNumber]);
+        let (tree, warnings) = read("x^23");
+        assert_eq!(tree, expr("x^2 * 3"));
+        assert_eq!(warnings, [Code::SplitNumber]);
+        assert_eq!(read("2e-3").1, [Code::SplitNumber]);
+        assert_eq!(read("2x3").1, [Code::SplitNumber]);
+
+        for source in ["x^{23}", "2x - 3", "2e", "\\frac12", "x^2 3", "x_12"] {
+            assert_eq!(read(source).1, [], "{}", source);
+        }
+    }
+}
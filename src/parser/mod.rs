@@ -1,5 +1,17 @@
+mod latex;
 mod token;
 mod tokenizer;
 
+pub(crate) use latex::*;
 pub(crate) use token::*;
 pub(crate) use tokenizer::*;
+
+/// Notation a formula is written in
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, clap::ValueEnum)]
+pub(crate) enum Syntax {
+    /// The expression language of fefy
+    #[default]
+    Expr,
+    /// A subset of LaTeX maths notation
+    Latex,
+}
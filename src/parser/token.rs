@@ -1,11 +1,21 @@
 use phf::{phf_map, Map};
 
+use crate::constants::Constant;
+
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) enum Token {
     Identifier(String),
-    IntLiteral(i64),
+    /// An identifier written in quotes, which is never a keyword or a constant
+    QuotedIdentifier(String),
+    IntLiteral(u64),
     FloatLiteral(f64),
+    /// An integer literal with the suffix `i64`, which is always a signed integer literal
+    SignedIntLiteral(i64),
+    /// A number literal with the suffix `f64`, which is always a 64-bit float literal
+    Float64Literal(f64),
     BoolLiteral(bool),
+    /// The symbol of a built-in constant such as `π`, which is the constant even without built-in constants
+    Constant(Constant),
     Plus,
     Minus,
     Asterisk,
@@ -25,6 +35,14 @@ pub(crate) enum Token {
     LessThanOrEqual,
     GreaterThan,
     GreaterThanOrEqual,
+    Semicolon,
+    Newline,
+    /// A root sign such as `√`, with the degree of the root
+    Radical(u8),
+    /// Superscript `²`
+    Squared,
+    /// Superscript `³`
+    Cubed,
 }
 const RESERVED_MAP: Map<&'static str, Token> = phf_map!(
     "true" => Token::BoolLiteral(true),
@@ -1,37 +1,95 @@
-use std::iter::Peekable;
+use std::collections::VecDeque;
 
-use anyhow::{anyhow, Result};
-use phf::{phf_set, Set};
+use anyhow::Result;
+use phf::{phf_map, phf_set, Map, Set};
 
 use super::token::Token;
+use crate::{
+    constants::Constant,
+    diagnostic::{Code, Diagnostic, Span},
+};
 const INVALID_IDENTIFIER_CHAR: Set<char> = phf_set!(
     '+', '-', '/', '\'', '*', '!', '@', '#', '$', '%', '^', '&', '(', ')', ';', ':', '<', '>', '=',
     '?', ',', '.', '\\', '|', '~', '`', '"'
 );
 
+/// Unicode operators read as the tokens of their ASCII forms, or as tokens without an ASCII form
+const UNICODE_OPERATOR_MAP: Map<char, Token> = phf_map!(
+    '×' => Token::Asterisk,
+    '·' => Token::Asterisk,
+    '⋅' => Token::Asterisk,
+    '∙' => Token::Asterisk,
+    '∗' => Token::Asterisk,
+    '÷' => Token::Slash,
+    '∕' => Token::Slash,
+    '−' => Token::Minus,
+    '≤' => Token::LessThanOrEqual,
+    '≥' => Token::GreaterThanOrEqual,
+    '≠' => Token::NotEquals,
+    '√' => Token::Radical(2),
+    '∛' => Token::Radical(3),
+    '∜' => Token::Radical(4),
+    '²' => Token::Squared,
+    '³' => Token::Cubed,
+);
+
+/// Characters that look like operators but aren't supported, with the ASCII form to use instead
+const LOOK_ALIKE_MAP: Map<char, &'static str> = phf_map!(
+    '‐' => "-",
+    '‑' => "-",
+    '‒' => "-",
+    '–' => "-",
+    '—' => "-",
+    '＋' => "+",
+    '＊' => "*",
+    '⁄' => "/",
+    '／' => "/",
+    '＝' => "=",
+    '（' => "(",
+    '）' => ")",
+    '⁰' => "^0",
+    '¹' => "^1",
+    '⁴' => "^4",
+    '⁵' => "^5",
+    '⁶' => "^6",
+    '⁷' => "^7",
+    '⁸' => "^8",
+    '⁹' => "^9",
+    '⁻' => "^-",
+);
+
 pub(crate) struct Tokens<C: Iterator<Item = char>> {
-    chars: Peekable<C>,
+    chars: C,
+    /// Chars peeked but not read yet
+    lookahead: VecDeque<char>,
     /// Number of chars already read (= index of the next char to read)
     read: usize,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct ParsedToken {
     pub(crate) token: Token,
     pub(crate) start: usize,
     pub(crate) end: usize,
 }
 
+impl ParsedToken {
+    pub(crate) fn span(&self) -> Span {
+        Span::new(self.start, self.end)
+    }
+}
+
 impl<C: Iterator<Item = char>> Tokens<C> {
     pub(crate) fn new(chars: C) -> Self {
         Self {
             read: 0,
-            chars: chars.peekable(),
+            chars,
+            lookahead: VecDeque::new(),
         }
     }
 
     fn next_char(&mut self) -> Option<char> {
-        let c = self.chars.next();
+        let c = self.lookahead.pop_front().or_else(|| self.chars.next());
         if c.is_some() {
             self.read += 1;
         }
@@ -39,7 +97,27 @@ impl<C: Iterator<Item = char>> Tokens<C> {
     }
 
     fn peek_char(&mut self) -> Option<&char> {
-        self.chars.peek()
+        self.peek_nth_char(0)
+    }
+
+    /// The char `n` chars after the next one, without reading any
+    fn peek_nth_char(&mut self, n: usize) -> Option<&char> {
+        while self.lookahead.len() <= n {
+            let c = self.chars.next()?;
+            self.lookahead.push_back(c);
+        }
+        self.lookahead.get(n)
+    }
+
+    /// Whether the next chars are the text, followed by a char that ends an identifier
+    fn peek_word(&mut self, text: &str) -> bool {
+        let count = text.chars().count();
+        text.chars()
+            .enumerate()
+            .all(|(n, c)| self.peek_nth_char(n) == Some(&c))
+            && self
+                .peek_nth_char(count)
+                .is_none_or(is_identifier_ending_char)
     }
 }
 
@@ -47,78 +125,292 @@ impl<C: Iterator<Item = char>> Iterator for Tokens<C> {
     type Item = Result<ParsedToken>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let first_char = match self.peek_char() {
-            Some(c) => c,
-            None => return None,
-        };
+        let first_char = self.peek_char()?;
         Some(match first_char {
+            '\n' => {
+                let start = self.read;
+                self.next_char();
+                Ok(ParsedToken {
+                    token: Token::Newline,
+                    start,
+                    end: self.read,
+                })
+            }
             c if c.is_whitespace() => {
                 self.next_char();
                 return self.next();
             }
-            c if c.is_ascii_digit() || *c == '.' => self.parse_number(),
+            '#' => {
+                self.skip_line_comment();
+                return self.next();
+            }
+            '/' => return self.parse_slash_or_block_comment(),
+            '\\' => return self.parse_backslash_or_line_continuation(),
+            c if c.is_ascii_digit() => self.parse_number(),
+            '.' => self.parse_period_or_number(),
+            '"' | '`' => self.parse_quoted_identifier(),
             c if INVALID_IDENTIFIER_CHAR.contains(c) => self.parse_reserved_char(),
+            c if UNICODE_OPERATOR_MAP.contains_key(c) || *c == 'π' => self.parse_unicode_char(),
+            c if LOOK_ALIKE_MAP.contains_key(c) => {
+                let c = *c;
+                let start = self.read;
+                self.next_char();
+                return Some(Err(unsupported_look_alike(c, start).into()));
+            }
             c if is_valid_identifier_starting_char(c) => self.parse_text(),
 
-            _ => return Some(Err(anyhow!("Unexpected character: {}", first_char))),
+            c => {
+                let c = *c;
+                let start = self.read;
+                self.next_char();
+                return Some(Err(unexpected_character(c, start).into()));
+            }
         })
     }
 }
 
 fn is_identifier_ending_char(char: &char) -> bool {
-    char.is_whitespace() || INVALID_IDENTIFIER_CHAR.contains(char)
+    char.is_whitespace()
+        || INVALID_IDENTIFIER_CHAR.contains(char)
+        || UNICODE_OPERATOR_MAP.contains_key(char)
+        || LOOK_ALIKE_MAP.contains_key(char)
+        || *char == 'π'
 }
 
 fn is_valid_identifier_starting_char(char: &char) -> bool {
-    !INVALID_IDENTIFIER_CHAR.contains(char)
+    !is_identifier_ending_char(char)
 }
 impl<C: Iterator<Item = char>> Tokens<C> {
+    /// Parses a decimal integer or float literal, or an integer literal with a `0x`, `0b` or `0o` prefix
     fn parse_number(&mut self) -> Result<ParsedToken> {
-        let mut parsed_string = String::new();
         let start = self.read;
+        let mut digits = String::new();
+
+        if self.peek_char() == Some(&'0') {
+            digits.push(self.next_char().expect("peek_char returned Some"));
+            let radix = match self.peek_char() {
+                Some('x' | 'X') => Some(Radix::Hexadecimal),
+                Some('b' | 'B') => Some(Radix::Binary),
+                Some('o' | 'O') => Some(Radix::Octal),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                self.next_char();
+                return self.parse_radix_number(start, radix);
+            }
+        }
+
+        self.take_digits(&mut digits, 10)?;
+        self.parse_decimal_number(start, digits, false)
+    }
+
+    /// Parses a period, or a float literal without an integer part such as `.5`
+    fn parse_period_or_number(&mut self) -> Result<ParsedToken> {
+        let start = self.read;
+        self.next_char();
+        match self.peek_char() {
+            Some(c) if c.is_ascii_digit() => {
+                self.parse_decimal_number(start, "0.".to_string(), true)
+            }
+            _ => Ok(ParsedToken {
+                token: Token::Period,
+                start,
+                end: self.read,
+            }),
+        }
+    }
+
+    /// Parses the rest of a decimal literal, `text` holds what was already read without underscores
+    fn parse_decimal_number(
+        &mut self,
+        start: usize,
+        mut text: String,
+        mut decimal_point: bool,
+    ) -> Result<ParsedToken> {
+        if decimal_point {
+            self.take_digits(&mut text, 10)?;
+        } else if self.peek_char() == Some(&'.') {
+            text.push(self.next_char().expect("peek_char returned Some"));
+            decimal_point = true;
+            self.take_digits(&mut text, 10)?;
+        }
+
+        // Without digits after it, an `e` is the name following the number, as in `2e` for `2 * e`
+        let exponent = self.exponent_follows();
+        if exponent {
+            text.push(self.next_char().expect("exponent_follows checked"));
+            if matches!(self.peek_char(), Some('+' | '-')) {
+                text.push(self.next_char().expect("peek_char returned Some"));
+            }
+            self.take_digits(&mut text, 10)?;
+        }
+
+        let suffix = self.take_suffix();
+        let end = self.read;
+        let token = if suffix == Some(Suffix::I64) {
+            if decimal_point || exponent {
+                return Err(Diagnostic::error(
+                    Code::InvalidNumber,
+                    "`i64` suffix on a float literal",
+                    Span::new(start, end),
+                )
+                .with_label("`i64` is only a suffix of integer literals")
+                .into());
+            }
+            Token::SignedIntLiteral(text.parse().map_err(|err: std::num::ParseIntError| {
+                Diagnostic::error(
+                    Code::InvalidNumber,
+                    "invalid `i64` literal",
+                    Span::new(start, end),
+                )
+                .with_label(err.to_string())
+            })?)
+        } else if decimal_point || exponent || suffix == Some(Suffix::F64) {
+            let value: f64 = text.parse().map_err(|err: std::num::ParseFloatError| {
+                Diagnostic::error(
+                    Code::InvalidNumber,
+                    "invalid float literal",
+                    Span::new(start, end),
+                )
+                .with_label(err.to_string())
+            })?;
+            if !value.is_finite() {
+                return Err(Diagnostic::error(
+                    Code::InvalidNumber,
+                    "float literal out of range",
+                    Span::new(start, end),
+                )
+                .with_label("too large to be represented as a float")
+                .into());
+            }
+            match suffix {
+                Some(Suffix::F64) => Token::Float64Literal(value),
+                _ => Token::FloatLiteral(value),
+            }
+        } else {
+            Token::IntLiteral(text.parse().map_err(|err: std::num::ParseIntError| {
+                Diagnostic::error(
+                    Code::InvalidNumber,
+                    "invalid integer literal",
+                    Span::new(start, end),
+                )
+                .with_label(err.to_string())
+            })?)
+        };
+        Ok(ParsedToken { token, start, end })
+    }
+
+    /// Whether an exponent follows, an `e` or `E` followed by digits with an optional sign
+    fn exponent_follows(&mut self) -> bool {
+        if !matches!(self.peek_char(), Some('e' | 'E')) {
+            return false;
+        }
+        let digit = match self.peek_nth_char(1) {
+            Some('+' | '-') => 2,
+            _ => 1,
+        };
+        self.peek_nth_char(digit).is_some_and(char::is_ascii_digit)
+    }
+
+    /// Reads the suffix fixing the encoding of a decimal literal, if one follows
+    fn take_suffix(&mut self) -> Option<Suffix> {
+        let suffix = [("i64", Suffix::I64), ("f64", Suffix::F64)]
+            .into_iter()
+            .find(|(text, _)| self.peek_word(text))
+            .map(|(_, suffix)| suffix)?;
+        for _ in 0..3 {
+            self.next_char();
+        }
+        Some(suffix)
+    }
 
-        let mut parsing_mode = NumberParsingState::NoDecimalPoint;
+    /// Parses the digits of an integer literal after its radix prefix
+    fn parse_radix_number(&mut self, start: usize, radix: Radix) -> Result<ParsedToken> {
+        let mut digits = String::new();
+        let digit_count = self.take_digits(&mut digits, radix.value())?;
 
+        if let Some(c) = self.peek_char().copied() {
+            if c.is_ascii_alphanumeric() {
+                let position = self.read;
+                self.next_char();
+                return Err(Diagnostic::error(
+                    Code::InvalidNumber,
+                    format!("invalid digit `{}` in {} literal", c, radix.name()),
+                    Span::new(position, position + 1),
+                )
+                .with_label(format!("not a {} digit", radix.name()))
+                .with_secondary(Span::new(start, position), "literal started here")
+                .into());
+            }
+        }
+        let end = self.read;
+        if digit_count == 0 {
+            return Err(Diagnostic::error(
+                Code::InvalidNumber,
+                format!("missing {} digits", radix.name()),
+                Span::new(start, end),
+            )
+            .with_label(format!("expected {} digits after the prefix", radix.name()))
+            .into());
+        }
+
+        let value = u64::from_str_radix(&digits, radix.value()).map_err(|err| {
+            Diagnostic::error(
+                Code::InvalidNumber,
+                "invalid integer literal",
+                Span::new(start, end),
+            )
+            .with_label(err.to_string())
+        })?;
+        Ok(ParsedToken {
+            token: Token::IntLiteral(value),
+            start,
+            end,
+        })
+    }
+
+    /// Reads digits of the radix into `text`, skipping single underscores between digits
+    ///
+    /// Returns the number of digits read.
+    fn take_digits(&mut self, text: &mut String, radix: u32) -> Result<usize> {
+        let mut count = 0;
         loop {
             match self.peek_char() {
-                Some(c) if c.is_ascii_digit() => {
-                    parsed_string.push(self.next_char().expect("peek_char returned Some"));
+                Some(c) if c.is_digit(radix) => {
+                    text.push(self.next_char().expect("peek_char returned Some"));
+                    count += 1;
                 }
-                Some('.') if parsing_mode == NumberParsingState::NoDecimalPoint => {
-                    parsed_string.push('.');
-                    parsing_mode = NumberParsingState::DecimalPoint;
+                Some('_') => {
+                    let position = self.read;
                     self.next_char();
+                    match self.peek_char() {
+                        Some(c) if count > 0 && c.is_digit(radix) => {}
+                        Some('_') => {
+                            return Err(Diagnostic::error(
+                                Code::InvalidNumber,
+                                "consecutive underscores in number literal",
+                                Span::new(position, position + 2),
+                            )
+                            .with_help("separate digits with a single underscore, as in `1_000`")
+                            .into())
+                        }
+                        _ => {
+                            return Err(Diagnostic::error(
+                                Code::InvalidNumber,
+                                "misplaced underscore in number literal",
+                                Span::new(position, position + 1),
+                            )
+                            .with_label("underscores are only allowed between digits")
+                            .into())
+                        }
+                    }
                 }
                 _ => break,
             }
         }
-        let end = self.read;
-        let token = match parsing_mode {
-            NumberParsingState::NoDecimalPoint => Token::IntLiteral(match parsed_string.parse() {
-                Ok(i) => i,
-                Err(err) => {
-                    return Err(anyhow!(
-                        "Failed to parse int literal at location {}..{}",
-                        start,
-                        end
-                    )
-                    .context(err))
-                }
-            }),
-            NumberParsingState::DecimalPoint => Token::FloatLiteral(match parsed_string.parse() {
-                Ok(f) => f,
-                Err(err) => {
-                    return Err(anyhow!(
-                        "Failed to parse float literal at location {}..{}",
-                        start,
-                        end
-                    )
-                    .context(err))
-                }
-            }),
-        };
-        Ok(ParsedToken { token, start, end })
+        Ok(count)
     }
+
     fn parse_text(&mut self) -> Result<ParsedToken> {
         let mut parsed_text = String::new();
 
@@ -148,30 +440,22 @@ impl<C: Iterator<Item = char>> Tokens<C> {
                     Token::Asterisk
                 }
             }
-            '/' => {
-                if self.peek_char() == Some(&'/') {
-                    self.next_char();
-                    Token::DoubleSlash
-                } else {
-                    Token::Slash
-                }
-            }
-            '\\' => Token::Backslash,
             '%' => Token::Percent,
             '^' => Token::Caret,
             '(' => Token::LeftParenthesis,
             ')' => Token::RightParenthesis,
             ',' => Token::Comma,
+            ';' => Token::Semicolon,
             '.' => Token::Period,
             '=' => Token::Equals,
             '!' => {
                 if self.peek_char() == Some(&'=') {
+                    self.next_char();
                     Token::NotEquals
                 } else {
-                    return Err(anyhow!(
-                        "Unexpected character: ! at {}. Hint: ! is only valid as part of \"!=\"",
-                        start
-                    ));
+                    return Err(unexpected_character('!', start)
+                        .with_help("`!` is only valid as part of `!=`")
+                        .into());
                 }
             }
 
@@ -191,14 +475,435 @@ impl<C: Iterator<Item = char>> Tokens<C> {
                     Token::GreaterThan
                 }
             }
-            _ => return Err(anyhow!("Unexpected character: {}", self.read)),
+            c => return Err(unexpected_character(c, start).into()),
         };
         let end = self.read;
         Ok(ParsedToken { token, start, end })
     }
 }
-#[derive(Debug, PartialEq, Eq)]
-enum NumberParsingState {
-    NoDecimalPoint,
-    DecimalPoint,
+impl<C: Iterator<Item = char>> Tokens<C> {
+    /// Skips a `#` comment up to the end of its line, the line break is still read as a token
+    fn skip_line_comment(&mut self) {
+        while self.peek_char().is_some_and(|c| *c != '\n') {
+            self.next_char();
+        }
+    }
+
+    /// Parses `/` or `//`, or skips a `/* */` comment and parses the token after it
+    fn parse_slash_or_block_comment(&mut self) -> Option<Result<ParsedToken>> {
+        let start = self.read;
+        self.next_char();
+        let token = match self.peek_char() {
+            Some('*') => {
+                self.next_char();
+                if let Err(error) = self.skip_block_comment(start) {
+                    return Some(Err(error));
+                }
+                return self.next();
+            }
+            Some('/') => {
+                self.next_char();
+                Token::DoubleSlash
+            }
+            _ => Token::Slash,
+        };
+        Some(Ok(ParsedToken {
+            token,
+            start,
+            end: self.read,
+        }))
+    }
+
+    /// Skips the rest of a block comment opened at `start`, up to and including the closing `*/`
+    fn skip_block_comment(&mut self, start: usize) -> Result<()> {
+        loop {
+            match self.next_char() {
+                Some('*') if self.peek_char() == Some(&'/') => {
+                    self.next_char();
+                    return Ok(());
+                }
+                Some(_) => {}
+                None => {
+                    return Err(Diagnostic::error(
+                        Code::UnclosedComment,
+                        "unclosed block comment",
+                        Span::new(start, start + 2),
+                    )
+                    .with_label("comment opened here")
+                    .with_secondary(Span::new(self.read, self.read), "expected `*/` here")
+                    .into())
+                }
+            }
+        }
+    }
+
+    /// Parses `\`, or skips a line continuation, a `\` at the end of a line, and parses the token after it
+    fn parse_backslash_or_line_continuation(&mut self) -> Option<Result<ParsedToken>> {
+        let start = self.read;
+        self.next_char();
+        while self
+            .peek_char()
+            .is_some_and(|c| *c != '\n' && c.is_whitespace())
+        {
+            self.next_char();
+        }
+        if self.peek_char() == Some(&'\n') {
+            self.next_char();
+            return self.next();
+        }
+        Some(Ok(ParsedToken {
+            token: Token::Backslash,
+            start,
+            end: start + 1,
+        }))
+    }
+}
+
+impl<C: Iterator<Item = char>> Tokens<C> {
+    /// Parses a Unicode operator, or `π` as the constant `pi`
+    fn parse_unicode_char(&mut self) -> Result<ParsedToken> {
+        let start = self.read;
+        let c = self.next_char().expect("peek_char returned Some");
+        let token = match c {
+            'π' => Token::Constant(Constant::Pi),
+            c => UNICODE_OPERATOR_MAP
+                .get(&c)
+                .cloned()
+                .ok_or_else(|| unexpected_character(c, start))?,
+        };
+        Ok(ParsedToken {
+            token,
+            start,
+            end: self.read,
+        })
+    }
+}
+
+impl<C: Iterator<Item = char>> Tokens<C> {
+    /// Parses an identifier in double quotes or backticks, with escape sequences for quotes, backslashes,
+    /// line breaks and tabs
+    fn parse_quoted_identifier(&mut self) -> Result<ParsedToken> {
+        let start = self.read;
+        let quote = self.next_char().expect("peek_char returned Some");
+        let mut name = String::new();
+        loop {
+            let position = self.read;
+            match self.next_char() {
+                Some(c) if c == quote => break,
+                Some('\\') => match self.next_char() {
+                    Some(c @ ('\\' | '"' | '`')) => name.push(c),
+                    Some('n') => name.push('\n'),
+                    Some('t') => name.push('\t'),
+                    Some(c) => {
+                        return Err(Diagnostic::error(
+                            Code::InvalidQuotedIdentifier,
+                            format!("unknown escape sequence `\\{}`", c),
+                            Span::new(position, self.read),
+                        )
+                        .with_help(
+                            "the escape sequences are `\\\\`, `\\\"`, `\\``, `\\n` and `\\t`",
+                        )
+                        .into())
+                    }
+                    None => return Err(unclosed_quoted_identifier(quote, start, self.read).into()),
+                },
+                Some(c) => name.push(c),
+                None => return Err(unclosed_quoted_identifier(quote, start, self.read).into()),
+            }
+        }
+
+        if name.is_empty() {
+            return Err(Diagnostic::error(
+                Code::InvalidQuotedIdentifier,
+                "empty quoted identifier",
+                Span::new(start, self.read),
+            )
+            .into());
+        }
+        Ok(ParsedToken {
+            token: Token::QuotedIdentifier(name),
+            start,
+            end: self.read,
+        })
+    }
+}
+
+fn unclosed_quoted_identifier(quote: char, start: usize, end: usize) -> Diagnostic {
+    Diagnostic::error(
+        Code::UnclosedQuotedIdentifier,
+        "unclosed quoted identifier",
+        Span::new(start, start + 1),
+    )
+    .with_label("quote opened here")
+    .with_secondary(Span::new(end, end), format!("expected `{}` here", quote))
+}
+
+/// Whether the name is read as a single unquoted identifier
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut tokens = Tokens::new(name.chars());
+    matches!(
+        (tokens.next(), tokens.next()),
+        (Some(Ok(parsed_token)), None) if parsed_token.token == Token::Identifier(name.to_string())
+    )
+}
+
+/// Writes the name in double quotes, escaping the characters that need it
+pub(crate) fn quote_identifier(name: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in name.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes the name as is if it is read as an identifier, quoted otherwise
+pub(crate) fn written_identifier(name: &str) -> String {
+    if is_identifier(name) {
+        name.to_string()
+    } else {
+        quote_identifier(name)
+    }
+}
+
+/// The name in a quoted identifier, if the text is a single quoted identifier
+pub(crate) fn unquote_identifier(text: &str) -> Option<String> {
+    let mut tokens = Tokens::new(text.chars());
+    match (tokens.next(), tokens.next()) {
+        (
+            Some(Ok(ParsedToken {
+                token: Token::QuotedIdentifier(name),
+                ..
+            })),
+            None,
+        ) => Some(name),
+        _ => None,
+    }
+}
+
+fn unsupported_look_alike(c: char, start: usize) -> Diagnostic {
+    let ascii = LOOK_ALIKE_MAP.get(&c).expect("Look-alike character");
+    Diagnostic::error(
+        Code::UnexpectedCharacter,
+        format!("unsupported character `{}`", c),
+        Span::new(start, start + 1),
+    )
+    .with_label(format!("looks like `{}`", ascii))
+    .with_help(format!("write `{}` instead", ascii))
+}
+
+pub(super) fn unexpected_character(c: char, start: usize) -> Diagnostic {
+    Diagnostic::error(
+        Code::UnexpectedCharacter,
+        format!("unexpected character `{}`", c),
+        Span::new(start, start + 1),
+    )
+}
+
+/// Suffix of a decimal literal fixing its encoding
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Suffix {
+    /// A signed integer literal, e.g. `5i64`
+    I64,
+    /// A 64-bit float literal, e.g. `0.5f64`
+    F64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Radix {
+    Binary,
+    Octal,
+    Hexadecimal,
+}
+
+impl Radix {
+    fn value(&self) -> u32 {
+        match self {
+            Radix::Binary => 2,
+            Radix::Octal => 8,
+            Radix::Hexadecimal => 16,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Radix::Binary => "binary",
+            Radix::Octal => "octal",
+            Radix::Hexadecimal => "hexadecimal",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quote_identifier, unquote_identifier, written_identifier, Tokens};
+    use crate::{
+        constants::Constant,
+        diagnostic::{Code, Diagnostic},
+        parser::Token,
+    };
+
+    fn tokens(text: &str) -> Vec<Token> {
+        Tokens::new(text.chars())
+            .map(|token| token.map(|token| token.token))
+            .collect::<anyhow::Result<_>>()
+            .unwrap_or_else(|error| panic!("`{}` can't be read: {:?}", text, error))
+    }
+
+    /// Code of the first error reading the text
+    fn error_code(text: &str) -> Code {
+        let error = Tokens::new(text.chars())
+            .find_map(Result::err)
+            .unwrap_or_else(|| panic!("`{}` is read", text));
+        error
+            .downcast_ref::<Diagnostic>()
+            .unwrap_or_else(|| panic!("`{}` fails without a diagnostic", text))
+            .code
+    }
+
+    #[test]
+    fn prefixed_integers_are_read_in_their_radix() {
+        assert_eq!(tokens("0x1F"), [Token::IntLiteral(31)]);
+        assert_eq!(tokens("0Xff_ff"), [Token::IntLiteral(0xffff)]);
+        assert_eq!(tokens("0b101"), [Token::IntLiteral(5)]);
+        assert_eq!(tokens("0o17"), [Token::IntLiteral(15)]);
+        assert_eq!(tokens("0"), [Token::IntLiteral(0)]);
+    }
+
+    #[test]
+    fn decimal_numbers_have_exponents_and_digit_separators() {
+        assert_eq!(tokens("1_000_000"), [Token::IntLiteral(1_000_000)]);
+        assert_eq!(tokens("1.5e3"), [Token::FloatLiteral(1500.0)]);
+        assert_eq!(tokens("2E-2"), [Token::FloatLiteral(0.02)]);
+        assert_eq!(tokens("1e+2"), [Token::FloatLiteral(100.0)]);
+        assert_eq!(tokens(".5"), [Token::FloatLiteral(0.5)]);
+        assert_eq!(tokens("5i64"), [Token::SignedIntLiteral(5)]);
+        assert_eq!(tokens("0.5f64"), [Token::Float64Literal(0.5)]);
+        // Without exponent digits, `e` is the name following the number
+        assert_eq!(
+            tokens("1e"),
+            [Token::IntLiteral(1), Token::Identifier("e".to_string())]
+        );
+        assert_eq!(
+            tokens("2e+x"),
+            [
+                Token::IntLiteral(2),
+                Token::Identifier("e".to_string()),
+                Token::Plus,
+                Token::Identifier("x".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_numbers_are_an_error() {
+        for text in [
+            "0x", "0b", "0b102", "0o8", "1__0", "1_", "1_.5", "1e400", "1.5i64",
+        ] {
+            assert_eq!(error_code(text), Code::InvalidNumber, "{}", text);
+        }
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let x = || Token::Identifier("x".to_string());
+        assert_eq!(tokens("x # squared\n"), [x(), Token::Newline]);
+        assert_eq!(
+            tokens("x /* times\n two */ * 2"),
+            [x(), Token::Asterisk, Token::IntLiteral(2)]
+        );
+        assert_eq!(
+            tokens("x // 2"),
+            [x(), Token::DoubleSlash, Token::IntLiteral(2)]
+        );
+        assert_eq!(tokens("/**/x"), [x()]);
+        assert_eq!(error_code("x /* times"), Code::UnclosedComment);
+    }
+
+    #[test]
+    fn line_continuations_join_lines() {
+        let x = || Token::Identifier("x".to_string());
+        assert_eq!(
+            tokens("x + \\\n 2"),
+            [x(), Token::Plus, Token::IntLiteral(2)]
+        );
+        assert_eq!(
+            tokens("x + \\  \n 2"),
+            [x(), Token::Plus, Token::IntLiteral(2)]
+        );
+        assert_eq!(
+            tokens("x \\ 2"),
+            [x(), Token::Backslash, Token::IntLiteral(2)]
+        );
+    }
+
+    #[test]
+    fn unicode_operators_are_read_as_their_tokens() {
+        let x = || Token::Identifier("x".to_string());
+        assert_eq!(tokens("x×x"), [x(), Token::Asterisk, x()]);
+        assert_eq!(tokens("x·x"), [x(), Token::Asterisk, x()]);
+        assert_eq!(tokens("x÷x"), [x(), Token::Slash, x()]);
+        assert_eq!(tokens("x−x"), [x(), Token::Minus, x()]);
+        assert_eq!(tokens("√x"), [Token::Radical(2), x()]);
+        assert_eq!(tokens("∛x"), [Token::Radical(3), x()]);
+        assert_eq!(tokens("x²"), [x(), Token::Squared]);
+        assert_eq!(tokens("x³"), [x(), Token::Cubed]);
+        assert_eq!(
+            tokens("2π"),
+            [Token::IntLiteral(2), Token::Constant(Constant::Pi)]
+        );
+        // The operators end names, so they don't need spaces around them
+        assert_eq!(tokens("xπ"), [x(), Token::Constant(Constant::Pi)]);
+    }
+
+    #[test]
+    fn look_alike_characters_are_an_error() {
+        for text in ["x – 1", "x＋1", "x⁴"] {
+            assert_eq!(error_code(text), Code::UnexpectedCharacter, "{}", text);
+        }
+    }
+
+    #[test]
+    fn quoted_identifiers_have_escape_sequences() {
+        let quoted = |name: &str| [Token::QuotedIdentifier(name.to_string())];
+        assert_eq!(tokens(r#""flow rate [m3/s]""#), quoted("flow rate [m3/s]"));
+        assert_eq!(tokens(r#""a \"b\"""#), quoted("a \"b\""));
+        assert_eq!(tokens(r#""back\\slash""#), quoted("back\\slash"));
+        assert_eq!(tokens(r#""line\nbreak\ttab""#), quoted("line\nbreak\ttab"));
+        assert_eq!(tokens(r"`tick\`s`"), quoted("tick`s"));
+        assert_eq!(tokens(r#"`"`"#), quoted("\""));
+        assert_eq!(tokens(r#""pi""#), quoted("pi"));
+
+        assert_eq!(error_code(r#""\q""#), Code::InvalidQuotedIdentifier);
+        assert_eq!(error_code(r#""""#), Code::InvalidQuotedIdentifier);
+        assert_eq!(error_code(r#""open"#), Code::UnclosedQuotedIdentifier);
+        assert_eq!(error_code(r#""open\"#), Code::UnclosedQuotedIdentifier);
+    }
+
+    #[test]
+    fn quoted_names_read_back_as_the_names() {
+        for name in [
+            "rate",
+            "flow rate",
+            "a \"b\"",
+            "back\\slash",
+            "line\nbreak\t",
+            "`",
+        ] {
+            assert_eq!(
+                unquote_identifier(&quote_identifier(name)).as_deref(),
+                Some(name)
+            );
+        }
+        assert_eq!(written_identifier("rate"), "rate");
+        assert_eq!(written_identifier("flow rate"), "\"flow rate\"");
+        assert_eq!(written_identifier("x2"), "x2");
+        assert_eq!(written_identifier("2x"), "\"2x\"");
+    }
 }
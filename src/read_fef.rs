@@ -0,0 +1,238 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::Infallible,
+    fmt::Display,
+    io::BufReader,
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use fef::v0::{
+    config::{OverridableConfig, DEFAULT_CONFIG},
+    expr::{
+        error::ComposeError,
+        traits::{Composer, ExprObj},
+        ExprTree, ExprVariable,
+    },
+    metadata::MetadataRecord,
+    raw::VariableLengthEnum,
+    read::{
+        read_configuration_with_default_configuration, read_expression_into_tree,
+        read_metadata_as_vec,
+    },
+    tokens::FileContentTypeToken,
+    traits::ReadFrom,
+};
+
+use crate::parser::{unquote_identifier, written_identifier};
+
+/// Contents of a Single Formula or Raw Formula FEF file
+pub(crate) struct FefFile {
+    pub(crate) metadata: Vec<MetadataRecord>,
+    pub(crate) expression: ExprTree,
+}
+
+impl FefFile {
+    pub(crate) fn read(file: &Path) -> Result<Self> {
+        let mut read_stream = BufReader::new(
+            std::fs::File::open(file).with_context(|| format!("Opening {}.", file.display()))?,
+        );
+
+        let version: usize = VariableLengthEnum::read_from(&mut read_stream, &DEFAULT_CONFIG)
+            .context("Reading version from file.")?
+            .try_into()
+            .context("Version parse")?;
+
+        if version != 0 {
+            bail!("Unsupported version: {}", version);
+        }
+
+        let content_type = FileContentTypeToken::read_from(&mut read_stream, &DEFAULT_CONFIG)
+            .context("Reading file content type from file.")?;
+
+        let configuration = match content_type {
+            FileContentTypeToken::SingleFormula => {
+                read_configuration_with_default_configuration(&mut read_stream)
+                    .context("Reading configuration from file.")?
+            }
+            FileContentTypeToken::RawFormula => OverridableConfig::default(),
+            _ => bail!("Unsupported file content type: {:?}", content_type),
+        };
+
+        let metadata = match content_type {
+            FileContentTypeToken::SingleFormula => {
+                read_metadata_as_vec(&mut read_stream, &configuration)
+                    .context("Reading metadata from file.")?
+            }
+            FileContentTypeToken::RawFormula => Vec::new(),
+            _ => bail!("Unsupported file content type: {:?}", content_type),
+        };
+
+        let expression = read_expression_into_tree(&mut read_stream, &configuration)
+            .context("Reading expression from file.")?;
+
+        Ok(Self {
+            metadata,
+            expression,
+        })
+    }
+
+    /// Name of the formula, if the file has a name record
+    pub(crate) fn name(&self) -> Result<Option<&str>> {
+        let names = self
+            .metadata
+            .iter()
+            .filter_map(|record| match record {
+                MetadataRecord::Name(name_record) => Some(name_record.name()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if names.len() > 1 {
+            bail!("Malformed FEF file: more than one name record.");
+        }
+
+        Ok(names.first().copied())
+    }
+
+    /// Identifiers and names of all named variables, in the order of their records
+    pub(crate) fn variable_names(&self) -> Vec<(&VariableLengthEnum, &str)> {
+        self.metadata
+            .iter()
+            .filter_map(|record| match record {
+                MetadataRecord::VariableName(variable_name_record) => Some((
+                    variable_name_record.variable_identifier(),
+                    variable_name_record.name(),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A variable of a formula, named or referred to by its identifier only
+pub(crate) struct Variable<'a> {
+    pub(crate) identifier: VariableLengthEnum,
+    pub(crate) name: Option<&'a str>,
+}
+
+impl Variable<'_> {
+    /// Whether the key refers to the variable, either by its name, possibly quoted, or by its identifier as `#id`
+    pub(crate) fn is_referred_to_by(&self, key: &str) -> bool {
+        if self.name == Some(key) {
+            return true;
+        }
+        if let (Some(name), Some(unquoted)) = (self.name, unquote_identifier(key)) {
+            return name == unquoted;
+        }
+        match key.strip_prefix('#').map(|id| id.parse::<usize>()) {
+            Some(Ok(id)) => VariableLengthEnum::from(id) == self.identifier,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Variable<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{}", written_identifier(name)),
+            None => write!(f, "#{}", self.identifier),
+        }
+    }
+}
+
+impl FefFile {
+    /// All variables used in the expression or named in the metadata, ordered by their identifiers
+    pub(crate) fn variables(&self) -> Result<Vec<Variable<'_>>> {
+        let mut collector = VariableCollector {
+            identifiers: BTreeSet::new(),
+        };
+        crate::compose_tree::compose_tree(&self.expression, &mut collector)
+            .context("Collecting variables of the expression.")?;
+
+        let names: BTreeMap<&VariableLengthEnum, &str> =
+            self.variable_names().into_iter().collect();
+
+        let mut identifiers = collector.identifiers;
+        identifiers.extend(names.keys().map(|identifier| (*identifier).clone()));
+
+        Ok(identifiers
+            .into_iter()
+            .map(|identifier| Variable {
+                name: names.get(&identifier).copied(),
+                identifier,
+            })
+            .collect())
+    }
+}
+
+/// Collects the identifiers of all variables of an expression
+struct VariableCollector {
+    identifiers: BTreeSet<VariableLengthEnum>,
+}
+
+impl Composer<()> for VariableCollector {
+    type Error = Infallible;
+
+    fn compose_default<E: ExprObj<()>>(
+        &mut self,
+        _expr: E,
+    ) -> Result<(), ComposeError<Self::Error>> {
+        Ok(())
+    }
+
+    fn compose_variable(
+        &mut self,
+        expr: ExprVariable<()>,
+    ) -> Result<(), ComposeError<Self::Error>> {
+        self.identifiers.insert(expr.into());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl FefFile {
+    /// File of the formula with the name, created with the default options
+    pub(crate) fn parse(name: &str, formula: &str) -> Self {
+        use fef::v0::metadata::{NameMetadataRecordObj, VariableNameMetadataRecordObj};
+
+        use crate::{
+            into_expr_tree::{
+                into_expr_tree, ImplicitMultiplication, LiteralEncoding, ParseOptions,
+                VariableOrder,
+            },
+            parser::Tokens,
+        };
+
+        let options = ParseOptions {
+            literals: LiteralEncoding::Exact,
+            fold_negative_literals: true,
+            implicit_multiplication: ImplicitMultiplication::Off,
+            builtin_constants: true,
+            variables: Vec::new(),
+            variable_order: VariableOrder::Appearance,
+            strict_variables: false,
+        };
+        let mut variable_names = Vec::new();
+        let expression = into_expr_tree(
+            &mut Tokens::new(formula.chars()),
+            &mut variable_names,
+            &options,
+            &mut Vec::new(),
+        )
+        .unwrap_or_else(|error| panic!("`{}` can't be parsed: {:?}", formula, error));
+
+        let metadata = std::iter::once(MetadataRecord::Name(NameMetadataRecordObj::new(
+            name.to_string(),
+        )))
+        .chain(variable_names.into_iter().enumerate().map(|(i, name)| {
+            MetadataRecord::VariableName(VariableNameMetadataRecordObj::new(name, i.into()))
+        }))
+        .collect();
+
+        Self {
+            metadata,
+            expression,
+        }
+    }
+}
@@ -1,164 +1,538 @@
 use std::{
     collections::BTreeMap,
-    io::{BufReader, Read, Write},
+    io::{IsTerminal, Read, Write},
     path::Path,
 };
 
-use anyhow::{bail, Context, Result};
-use fef::v0::{
-    config::{OverridableConfig, DEFAULT_CONFIG},
-    metadata::MetadataRecord,
-    raw::VariableLengthEnum,
-    read::{read_configuration_with_default_configuration, read_metadata_as_vec},
-    tokens::FileContentTypeToken,
-    traits::ReadFrom,
-};
-
-pub(crate) fn write_to_file_from_stdin(file: &Path) -> Result<()> {
-    let mut write_stream = std::fs::File::create(file)?;
+use anyhow::{anyhow, bail, Context, Result};
+use fef::v0::{config::DEFAULT_CONFIG, raw::VariableLengthEnum};
 
-    let name = {
-        print!("Enter name for formula:");
+use crate::{
+    codegen::{CodegenOptions, Language},
+    export::ExportFormat,
+    into_expr_tree::ParseOptions,
+    parser::{ParsedToken, Syntax},
+    read_fef::{FefFile, Variable},
+};
 
-        crate::cl_tools::read_line()
+/// Creates a FEF file from a formula given as an expression, in a file or on the standard input
+///
+/// The formula is written in the expression language or in LaTeX maths notation, depending on the syntax.
+/// The name and the formula are prompted for only when they weren't given and the standard input is a terminal.
+/// Otherwise a missing formula is read from the standard input and a missing name is left out.
+pub(crate) fn create_file(
+    out_file: &Path,
+    in_file: Option<&Path>,
+    expr: Option<String>,
+    name: Option<String>,
+    no_name: bool,
+    syntax: Syntax,
+    options: &ParseOptions,
+) -> Result<()> {
+    let interactive = std::io::stdin().is_terminal();
+
+    let name = match name {
+        Some(name) => Some(name),
+        None if !no_name && interactive => {
+            print!("Enter name for formula:");
+            Some(crate::cl_tools::read_line().trim().to_string())
+        }
+        None => None,
     }
-    .trim()
-    .to_string();
-
-    let name = if name.is_empty() { None } else { Some(name) };
-
-    let formula = {
-        print!("Enter formula:");
-        crate::cl_tools::read_line()
+    .filter(|name| !name.is_empty());
+
+    let (formula, origin) = match (expr, in_file) {
+        (Some(expr), _) => (expr, "<expr>".to_string()),
+        (None, Some(in_file)) => {
+            let mut formula: String = String::new();
+            std::fs::File::open(in_file)
+                .with_context(|| format!("Opening {}.", in_file.display()))?
+                .read_to_string(&mut formula)?;
+            (formula, in_file.display().to_string())
+        }
+        (None, None) if interactive => {
+            print!("Enter formula:");
+            (crate::cl_tools::read_line(), "<stdin>".to_string())
+        }
+        (None, None) => {
+            let mut formula: String = String::new();
+            std::io::stdin().read_to_string(&mut formula)?;
+            (formula, "<stdin>".to_string())
+        }
     };
 
-    let input_chars = formula.chars();
-    let mut tokens = crate::parser::Tokens::new(input_chars);
+    let mut warnings = Vec::new();
+    let mut tokens: Box<dyn Iterator<Item = Result<ParsedToken>>> = match syntax {
+        Syntax::Expr => Box::new(crate::parser::Tokens::new(formula.chars())),
+        Syntax::Latex => Box::new(crate::parser::LatexTokens::new(&formula, &mut warnings)),
+    };
 
-    crate::write_as_fef::write_tokens_as_fef_to_stream(&mut tokens, &mut write_stream, name)?;
+    // The formula is written to memory first, so that no file is created when it can't be parsed
+    let mut buffer: Vec<u8> = Vec::new();
+    let written = crate::write_as_fef::write_tokens_as_fef_to_stream(
+        &mut tokens,
+        &mut buffer,
+        name,
+        options,
+        &mut warnings,
+        &DEFAULT_CONFIG,
+    );
+    for warning in &warnings {
+        eprint!("{}", warning.render(&formula, &origin));
+    }
+    written.map_err(|error| crate::diagnostic::report(error, &formula, &origin))?;
 
+    let mut write_stream = std::fs::File::create(out_file)
+        .with_context(|| format!("Creating {}.", out_file.display()))?;
+    write_stream.write_all(&buffer)?;
     write_stream.flush()?;
 
     Ok(())
 }
 
-pub(crate) fn write_to_file_from_file(in_file: &Path, out_file: &Path) -> Result<()> {
-    let mut write_stream = std::fs::File::create(out_file)?;
+/// Evaluates a FEF file, taking values of variables from the variables file, the given values and finally the user
+pub(crate) fn evaluate_from_file(
+    file: &Path,
+    vars_file: Option<&Path>,
+    vars: &[(String, f64)],
+    prompt: bool,
+) -> Result<()> {
+    let fef_file = FefFile::read(file)?;
+    let name = fef_file.name()?;
+    let variables = fef_file.variables()?;
+
+    let mut variable_values = given_variable_values(&variables, vars_file, vars)?;
 
-    let name = {
-        print!("Enter name for formula:");
+    let missing_variables: Vec<&Variable> = variables
+        .iter()
+        .filter(|variable| !variable_values.contains_key(&variable.identifier))
+        .collect();
 
-        crate::cl_tools::read_line()
+    if !prompt && !missing_variables.is_empty() {
+        bail!(
+            "Missing values for variables: {}",
+            list(missing_variables.into_iter())
+        );
     }
-    .trim()
-    .to_string();
 
-    let name = if name.is_empty() { None } else { Some(name) };
+    if let Some(name) = name {
+        println!("============================================================");
+        println!("Evaluating {}", name);
+    }
 
-    let mut formula: String = String::new();
+    if !missing_variables.is_empty() {
+        println!("============================================================");
+        println!("Enter values for variables:");
+        println!("------------------------------------------------------------");
+    }
 
-    std::fs::File::open(in_file)?.read_to_string(&mut formula)?;
+    for variable in missing_variables {
+        print!("Enter value for variable {}:", variable);
+        let value = crate::cl_tools::read_line().trim().parse::<f64>()?;
+        variable_values.insert(variable.identifier.clone(), value);
+    }
 
-    let input_chars = formula.chars();
-    let mut tokens = crate::parser::Tokens::new(input_chars);
+    println!("============================================================");
 
-    crate::write_as_fef::write_tokens_as_fef_to_stream(&mut tokens, &mut write_stream, name)?;
+    let result = crate::evaluate_fef_stream::evaluate_tree_as_fef_expr(
+        &fef_file.expression,
+        variable_values,
+    )?;
+    if result.is_nan() {
+        bail!("Result is not a number");
+    }
 
-    write_stream.flush()?;
+    println!("Result: {}", result);
+
+    println!("============================================================");
 
     Ok(())
 }
 
-pub(crate) fn evaluate_from_file(file: &Path) -> Result<()> {
-    let mut read_stream = BufReader::new(std::fs::File::open(file)?);
+/// Evaluates a FEF file for each row of a CSV file, writing the rows with an added result column
+///
+/// Columns are matched to variables by their header. Values of variables without a column are taken
+/// from the variables file and the given values. Rows that can't be evaluated are reported and left
+/// with an empty result.
+pub(crate) fn evaluate_csv_from_file(
+    file: &Path,
+    vars_file: Option<&Path>,
+    vars: &[(String, f64)],
+    csv_file: &Path,
+    out_file: Option<&Path>,
+) -> Result<()> {
+    let fef_file = FefFile::read(file)?;
+    let variables = fef_file.variables()?;
+
+    let constant_values = given_variable_values(&variables, vars_file, vars)?;
+
+    let mut reader = csv::Reader::from_path(csv_file)
+        .with_context(|| format!("Opening {}.", csv_file.display()))?;
+    let headers = reader
+        .headers()
+        .with_context(|| format!("Reading header of {}.", csv_file.display()))?
+        .clone();
+
+    let columns: Vec<(usize, &Variable)> = headers
+        .iter()
+        .enumerate()
+        .flat_map(|(column, header)| {
+            variables
+                .iter()
+                .filter(move |variable| variable.is_referred_to_by(header.trim()))
+                .map(move |variable| (column, variable))
+        })
+        .collect();
 
-    let version: usize = VariableLengthEnum::read_from(&mut read_stream, &DEFAULT_CONFIG)
-        .context("Reading version from file.")?
-        .try_into()
-        .context("Version parse")?;
+    let missing_variables: Vec<&Variable> = variables
+        .iter()
+        .filter(|variable| {
+            !constant_values.contains_key(&variable.identifier)
+                && !columns
+                    .iter()
+                    .any(|(_, column_variable)| column_variable.identifier == variable.identifier)
+        })
+        .collect();
 
-    if version != 0 {
-        bail!("Unsupported version: {}", version);
+    if !missing_variables.is_empty() {
+        bail!(
+            "Missing values for variables: {}. Add a column for them to {} or give their values with --var.",
+            list(missing_variables.into_iter()),
+            csv_file.display()
+        );
     }
 
-    let file_content_type = FileContentTypeToken::read_from(&mut read_stream, &DEFAULT_CONFIG)
-        .context("Reading file content type from file.")?;
-
-    let configuration = match file_content_type {
-        FileContentTypeToken::SingleFormula => {
-            read_configuration_with_default_configuration(&mut read_stream)
-                .context("Reading configuration from file.")?
-        }
-        FileContentTypeToken::RawFormula => OverridableConfig::default(),
-        _ => bail!("Unsupported file content type: {:?}", file_content_type),
+    let output: Box<dyn Write> = match out_file {
+        Some(out_file) => Box::new(
+            std::fs::File::create(out_file)
+                .with_context(|| format!("Creating {}.", out_file.display()))?,
+        ),
+        None => Box::new(std::io::stdout()),
     };
-
-    let metadata_vec = match file_content_type {
-        FileContentTypeToken::SingleFormula => {
-            read_metadata_as_vec(&mut read_stream, &configuration)
-                .context("Reading metadata from file.")?
+    let mut writer = csv::Writer::from_writer(output);
+
+    let mut output_headers = headers.clone();
+    output_headers.push_field("result");
+    writer.write_record(&output_headers)?;
+
+    let mut rows = 0;
+    let mut failed_rows = 0;
+
+    for record in reader.records() {
+        rows += 1;
+
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                failed_rows += 1;
+                continue;
+            }
+        };
+
+        let mut variable_values = constant_values.clone();
+        let result = columns
+            .iter()
+            .try_for_each(|(column, variable)| {
+                let value = record[*column].trim();
+                let value = value.parse::<f64>().with_context(|| {
+                    format!("Invalid value '{}' of variable {}", value, variable)
+                })?;
+                variable_values.insert(variable.identifier.clone(), value);
+                Ok(())
+            })
+            .and_then(|_| {
+                crate::evaluate_fef_stream::evaluate_tree_as_fef_expr(
+                    &fef_file.expression,
+                    variable_values,
+                )
+            })
+            .and_then(|result| match result.is_nan() {
+                true => Err(anyhow!("Result is not a number")),
+                false => Ok(result),
+            });
+
+        let mut output_record = record.clone();
+        match result {
+            Ok(result) => output_record.push_field(&result.to_string()),
+            Err(e) => {
+                let line = record
+                    .position()
+                    .map(|position| position.line())
+                    .unwrap_or(0);
+                eprintln!("Error on line {}: {:#}", line, e);
+                failed_rows += 1;
+                output_record.push_field("");
+            }
         }
-        FileContentTypeToken::RawFormula => Vec::new(),
-        _ => bail!("Unsupported file content type: {:?}", file_content_type),
-    };
+        writer.write_record(&output_record)?;
+    }
 
-    let names = metadata_vec
-        .iter()
-        .filter_map(|record| match record {
-            MetadataRecord::Name(name_record) => Some(name_record.name()),
-            _ => None,
-        })
-        .collect::<Vec<_>>();
+    writer.flush()?;
 
-    let variable_names: Vec<(&VariableLengthEnum, &str)> = metadata_vec
-        .iter()
-        .filter_map(|record| match record {
-            MetadataRecord::VariableName(variable_name_record) => Some((
-                variable_name_record.variable_identifier(),
-                variable_name_record.name(),
-            )),
-            _ => None,
-        })
-        .collect();
+    if failed_rows > 0 {
+        bail!("{} of {} rows could not be evaluated", failed_rows, rows);
+    }
+
+    Ok(())
+}
 
-    if names.len() > 1 {
-        bail!("Malformed FEF file: more than one name record.");
+/// Prints the formula of a FEF file in the expression language, optionally preceded by its name as a header comment
+pub(crate) fn decompile_file(file: &Path, header: bool) -> Result<()> {
+    let fef_file = FefFile::read(file)?;
+    let name = fef_file.name()?;
+    let text = crate::decompile::decompile(&fef_file.expression, &fef_file.variable_names())?;
+
+    if let (true, Some(name)) = (header, name) {
+        // Every line of the name is commented, so the output can be read back as a formula
+        for line in name.lines() {
+            println!("# {}", line);
+        }
     }
+    println!("{}", text);
 
-    if names.len() == 1 {
-        println!("============================================================");
-        println!("Evaluating {}", names[0]);
+    Ok(())
+}
+
+/// Prints the structure of a FEF file with the bytes of each of its parts, as text or as JSON
+pub(crate) fn inspect_file(file: &Path, json: bool) -> Result<()> {
+    let bytes = std::fs::read(file).with_context(|| format!("Reading {}.", file.display()))?;
+    let inspection = crate::inspect::Inspection::read(&bytes)?;
+
+    if json {
+        println!("{}", inspection.to_json()?);
+    } else {
+        print!("{}", inspection.to_text());
     }
 
-    if variable_names.len() > 0 {
-        println!("============================================================");
-        println!("Enter values for variables:");
-        println!("------------------------------------------------------------");
+    Ok(())
+}
+
+/// Writes the formula of a FEF file in the format to the output file or the standard output
+pub(crate) fn export_file(
+    file: &Path,
+    format: ExportFormat,
+    out_file: Option<&Path>,
+) -> Result<()> {
+    let fef_file = FefFile::read(file)?;
+    let text = crate::export::export(&fef_file, format)?;
+
+    match out_file {
+        Some(out_file) => std::fs::write(out_file, format!("{}\n", text))
+            .with_context(|| format!("Writing {}.", out_file.display()))?,
+        None => println!("{}", text),
     }
 
-    let mut variable_values: BTreeMap<VariableLengthEnum, f64> = BTreeMap::new();
+    Ok(())
+}
 
-    for (variable_identifier, variable_name) in variable_names.iter() {
-        print!("Enter value for variable '{}':", variable_name);
-        let value = crate::cl_tools::read_line().trim().parse::<f64>()?;
-        variable_values.insert(variable_identifier.to_owned().clone(), value);
+/// Writes source files computing the formula of a FEF file next to the output file or to the standard output
+pub(crate) fn codegen_file(
+    file: &Path,
+    language: Language,
+    out_file: Option<&Path>,
+    options: &CodegenOptions,
+) -> Result<()> {
+    let fef_file = FefFile::read(file)?;
+    let stem = out_file
+        .and_then(|out_file| out_file.file_stem())
+        .and_then(|stem| stem.to_str());
+    let source_files = crate::codegen::codegen(&fef_file, language, stem, options)?;
+
+    for (index, source_file) in source_files.iter().enumerate() {
+        match out_file {
+            // Languages with several files, such as C, write each with its own extension
+            Some(out_file) => {
+                let path = match source_files.len() {
+                    1 => out_file.to_path_buf(),
+                    _ => out_file.with_extension(source_file.extension),
+                };
+                std::fs::write(&path, &source_file.source)
+                    .with_context(|| format!("Writing {}.", path.display()))?
+            }
+            None => {
+                if index > 0 {
+                    println!();
+                }
+                print!("{}", source_file.source)
+            }
+        }
     }
 
-    println!("============================================================");
+    Ok(())
+}
 
-    let result =
-        crate::evaluate_fef_stream::evaluate_stream_as_fef_expr(&mut read_stream, variable_values);
+/// Collects values of variables given in the variables file and on the command line
+///
+/// Variables are given by their name or by their identifier as `#id`. Values given on the command line
+/// take precedence. Names that aren't variables of the formula are rejected.
+fn given_variable_values(
+    variables: &[Variable],
+    vars_file: Option<&Path>,
+    vars: &[(String, f64)],
+) -> Result<BTreeMap<VariableLengthEnum, f64>> {
+    let mut given_values: Vec<(String, f64)> = match vars_file {
+        Some(vars_file) => read_variable_values_file(vars_file)?,
+        None => Vec::new(),
+    };
+    given_values.extend(vars.iter().cloned());
+
+    let mut variable_values: BTreeMap<VariableLengthEnum, f64> = BTreeMap::new();
 
-    match result {
-        Ok(result) => {
-            println!("Result: {}", result);
+    for (key, value) in given_values {
+        let referred_variables: Vec<&Variable> = variables
+            .iter()
+            .filter(|variable| variable.is_referred_to_by(&key))
+            .collect();
+        if referred_variables.is_empty() {
+            bail!(
+                "Unknown variable '{}'. The variables of the formula are: {}",
+                key,
+                list(variables.iter())
+            );
         }
-        Err(e) => {
-            eprintln!("Error: {}", e);
+        for variable in referred_variables {
+            variable_values.insert(variable.identifier.clone(), value);
         }
     }
 
-    println!("============================================================");
+    Ok(variable_values)
+}
 
-    Ok(())
+fn list<'a>(variables: impl Iterator<Item = &'a Variable<'a>>) -> String {
+    variables
+        .map(|variable| variable.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Reads `name=value` pairs from a file, one per line
+///
+/// Empty lines and comments, lines starting with `#` not followed by a digit, are ignored.
+fn read_variable_values_file(file: &Path) -> Result<Vec<(String, f64)>> {
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Reading variables file {}.", file.display()))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            let is_comment =
+                line.starts_with('#') && !line[1..].starts_with(|c: char| c.is_ascii_digit());
+            !line.is_empty() && !is_comment
+        })
+        .map(|(index, line)| {
+            crate::arguments::parse_variable_value(line)
+                .map_err(|error| anyhow!("{}:{}: {}", file.display(), index + 1, error))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use fef::v0::raw::VariableLengthEnum;
+
+    use super::{create_file, evaluate_csv_from_file, evaluate_from_file, given_variable_values};
+    use crate::{
+        into_expr_tree::{ImplicitMultiplication, LiteralEncoding, ParseOptions, VariableOrder},
+        parser::Syntax,
+        read_fef::FefFile,
+    };
+
+    /// A path in the temporary directory, unique to the test process
+    fn temporary_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("fefy-test-{}-{}", std::process::id(), name))
+    }
+
+    /// Creates a FEF file of the formula in the temporary directory
+    fn create(file_name: &str, formula: &str) -> PathBuf {
+        let options = ParseOptions {
+            literals: LiteralEncoding::Exact,
+            fold_negative_literals: true,
+            implicit_multiplication: ImplicitMultiplication::Off,
+            builtin_constants: true,
+            variables: Vec::new(),
+            variable_order: VariableOrder::Appearance,
+            strict_variables: false,
+        };
+        let path = temporary_path(file_name);
+        create_file(
+            &path,
+            None,
+            Some(formula.to_string()),
+            Some("Test".to_string()),
+            false,
+            Syntax::Expr,
+            &options,
+        )
+        .expect("Formula is created");
+        path
+    }
+
+    #[test]
+    fn values_are_given_in_the_file_and_on_the_command_line() {
+        let fef_path = create("given.fef", "a * b + \"flow rate\" + c");
+        let fef_file = FefFile::read(&fef_path).unwrap();
+        let variables = fef_file.variables().unwrap();
+        let vars_path = temporary_path("given.vars");
+        std::fs::write(&vars_path, "# values\n\na = 1\nb=2\n#3 = 4\n").unwrap();
+
+        let vars = [("b".to_string(), 5.0), ("\"flow rate\"".to_string(), 6.0)];
+        let values = given_variable_values(&variables, Some(&vars_path), &vars).unwrap();
+        let expected = [(0, 1.0), (1, 5.0), (2, 6.0), (3, 4.0)]
+            .map(|(identifier, value)| (VariableLengthEnum::from(identifier), value));
+        assert_eq!(values, expected.into_iter().collect());
+
+        let unknown = [("d".to_string(), 1.0)];
+        assert!(given_variable_values(&variables, None, &unknown).is_err());
+        std::fs::write(&vars_path, "a = one\n").unwrap();
+        assert!(given_variable_values(&variables, Some(&vars_path), &[]).is_err());
+
+        std::fs::remove_file(fef_path).unwrap();
+        std::fs::remove_file(vars_path).unwrap();
+    }
+
+    #[test]
+    fn evaluation_fails_without_a_result() {
+        let path = create("quotient.fef", "a / b");
+        let values = |a: f64, b: f64| [("a".to_string(), a), ("b".to_string(), b)];
+        assert!(evaluate_from_file(&path, None, &values(1.0, 2.0), false).is_ok());
+        assert!(evaluate_from_file(&path, None, &values(0.0, 0.0), false).is_err());
+        assert!(evaluate_from_file(&path, None, &values(1.0, 2.0)[..1], false).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rows_of_a_csv_file_are_evaluated() {
+        let fef_path = create("rows.fef", "a / b + c");
+        let csv_path = temporary_path("rows.csv");
+        let out_path = temporary_path("rows.out.csv");
+        let vars = [("c".to_string(), 1.0)];
+
+        std::fs::write(&csv_path, "a,b,label\n1,2,first\n3, 4 ,second\n").unwrap();
+        evaluate_csv_from_file(&fef_path, None, &vars, &csv_path, Some(&out_path)).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out_path).unwrap(),
+            "a,b,label,result\n1,2,first,1.5\n3, 4 ,second,1.75\n"
+        );
+
+        // Rows that can't be evaluated are left without a result and fail the evaluation
+        std::fs::write(&csv_path, "a,b\n1,2\n0,0\nx,1\n3,0\n").unwrap();
+        assert!(
+            evaluate_csv_from_file(&fef_path, None, &vars, &csv_path, Some(&out_path)).is_err()
+        );
+        assert_eq!(
+            std::fs::read_to_string(&out_path).unwrap(),
+            "a,b,result\n1,2,1.5\n0,0,\nx,1,\n3,0,inf\n"
+        );
+
+        // Variables without a column must be given
+        assert!(evaluate_csv_from_file(&fef_path, None, &[], &csv_path, Some(&out_path)).is_err());
+
+        for path in [fef_path, csv_path, out_path] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
 }
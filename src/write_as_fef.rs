@@ -2,20 +2,24 @@ use std::io::Write;
 
 use anyhow::{Context, Ok, Result};
 use fef::v0::{
-    config::DEFAULT_CONFIG,
+    config::Config,
     metadata::{MetadataRecord, NameMetadataRecordObj, VariableNameMetadataRecordObj},
     write::write_metadata_vec_expression_tree_as_single_formula,
 };
 
-use crate::parser::ParsedToken;
+use crate::{diagnostic::Diagnostic, into_expr_tree::ParseOptions, parser::ParsedToken};
 
 pub(crate) fn write_tokens_as_fef_to_stream(
     tokens: &mut impl Iterator<Item = Result<ParsedToken>>,
     stream: &mut impl Write,
     name: Option<String>,
+    options: &ParseOptions,
+    warnings: &mut Vec<Diagnostic>,
+    configuration: &impl Config,
 ) -> Result<()> {
     let mut variable_names: Vec<String> = Vec::new();
-    let expr_tree = crate::into_expr_tree::into_expr_tree(tokens, &mut variable_names, false)?;
+    let expr_tree =
+        crate::into_expr_tree::into_expr_tree(tokens, &mut variable_names, options, warnings)?;
 
     let name_metadata_record =
         name.map(|name| MetadataRecord::Name(NameMetadataRecordObj::new(name)));
@@ -30,13 +34,13 @@ pub(crate) fn write_tokens_as_fef_to_stream(
 
     let metadata_records: Vec<_> = name_metadata_record
         .into_iter()
-        .chain(variable_names_metadata_records.into_iter())
+        .chain(variable_names_metadata_records)
         .collect();
 
     write_metadata_vec_expression_tree_as_single_formula(
         stream,
         &expr_tree,
-        &DEFAULT_CONFIG,
+        configuration,
         &metadata_records,
     )
     .context("FEF Write Error")?;
@@ -0,0 +1,90 @@
+use crate::into_expr_tree::Operator;
+
+/// Text of a written subexpression and how tightly it binds, to write expressions with the fewest parentheses
+/// needed
+///
+/// The expression language, the exported notations and the generated code each write their own text and
+/// parentheses, the precedences are those of the operators of the expression language.
+pub(crate) struct Written {
+    pub(crate) text: String,
+    pub(crate) precedence: usize,
+    /// Whether the text starts with a prefix unary operator, such as a minus sign
+    pub(crate) unary: bool,
+    /// Whether a superscript can follow the text without parentheses
+    pub(crate) superscriptable: bool,
+}
+
+/// Precedence of literals, variables, function calls and parenthesised expressions
+pub(crate) const ATOM_PRECEDENCE: usize = usize::MAX;
+
+impl Written {
+    pub(crate) fn atom(text: String) -> Self {
+        Self {
+            text,
+            precedence: ATOM_PRECEDENCE,
+            unary: false,
+            superscriptable: true,
+        }
+    }
+
+    /// An atom a superscript can't follow, such as a fraction
+    pub(crate) fn closed(text: String) -> Self {
+        Self {
+            superscriptable: false,
+            ..Self::atom(text)
+        }
+    }
+
+    /// An operation binding with the precedence
+    pub(crate) fn compound(text: String, precedence: usize) -> Self {
+        Self {
+            text,
+            precedence,
+            unary: false,
+            superscriptable: false,
+        }
+    }
+
+    /// An operation starting with a prefix unary operator binding with the precedence
+    pub(crate) fn prefixed(text: String, precedence: usize) -> Self {
+        Self {
+            unary: true,
+            ..Self::compound(text, precedence)
+        }
+    }
+
+    /// A literal, which is written with a leading minus sign if it is negative
+    pub(crate) fn number(text: String) -> Self {
+        if text.starts_with('-') {
+            Self::prefixed(text, negation_precedence())
+        } else {
+            Self::atom(text)
+        }
+    }
+
+    /// The text in parentheses
+    pub(crate) fn parenthesised(self) -> String {
+        self.enclosed("(", ")")
+    }
+
+    /// The text between the delimiters, for notations with other parentheses
+    pub(crate) fn enclosed(self, open: &str, close: &str) -> String {
+        format!("{}{}{}", open, self.text, close)
+    }
+
+    /// The text as an operand of any operation, parenthesised unless it is an atom
+    pub(crate) fn operand(self) -> String {
+        if self.precedence == ATOM_PRECEDENCE {
+            self.text
+        } else {
+            self.parenthesised()
+        }
+    }
+}
+
+/// Precedence of a negation
+pub(crate) fn negation_precedence() -> usize {
+    Operator::Minus
+        .unary_precedence()
+        .expect("Minus is a unary operator")
+}